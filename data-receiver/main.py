@@ -1,8 +1,10 @@
+import argparse
 import serial
 import serial.tools.list_ports
 import sys
 import time
 import os
+import subprocess
 import threading
 
 BAUD_RATE = 115200
@@ -24,13 +26,31 @@ def stdout_open():
     except (OSError, ValueError):
         return False
 
-def monitor(port: str, log_path: str):
+def decoder_command():
+    # built on first use, the framed output of SET output binary is turned back into text by it
+    manifest = os.path.join(LOG_DIR, "..", "Cargo.toml")
+    return ["cargo", "run", "--quiet", "--release", "--manifest-path", manifest, "-p", "ee-decoder"]
+
+def log_line(log, decoded: str):
+    log.write(decoded + "\n")
+    if stdout_open():
+        print(decoded, flush=True)
+    if decoded == "=== Experiment Complete ===":
+        stop_event.set()
+
+def monitor(port: str, log_path: str, commands: list, binary: bool):
     with serial.Serial(port, BAUD_RATE, timeout=0) as ser, open(log_path, "w", buffering=1) as log:
         time.sleep(2)
-        ser.write(b"START")
-        log.write(">> START\n")
-        if stdout_open():
-            print(">> START", flush=True)
+        # the firmware answers each line with OK or ERR
+        for command in commands:
+            ser.write(f"{command}\n".encode())
+            log.write(f">> {command}\n")
+            if stdout_open():
+                print(f">> {command}", flush=True)
+
+        if binary:
+            monitor_frames(ser, log)
+            return
 
         buffer = bytearray()
         while not stop_event.is_set():
@@ -39,30 +59,83 @@ def monitor(port: str, log_path: str):
                 buffer.extend(data)
                 while b"\n" in buffer:
                     line, buffer = buffer.split(b"\n", 1)
-                    decoded = line.decode("utf-8", errors="replace").rstrip()
-                    log.write(decoded + "\n")
-                    if stdout_open():
-                        print(decoded, flush=True)
-                    if decoded == "=== Experiment Complete ===":
-                        stop_event.set()
+                    log_line(log, line.decode("utf-8", errors="replace").rstrip())
+                    if stop_event.is_set():
                         return
 
+def monitor_frames(ser, log):
+    # the raw bytes are kept next to the log so a capture can be decoded again
+    capture_path = os.path.join(LOG_DIR, "data.bin")
+    with open(capture_path, "wb") as capture:
+        decoder = subprocess.Popen(decoder_command(), stdin=subprocess.PIPE, stdout=subprocess.PIPE)
+
+        def read_decoded():
+            for line in decoder.stdout:
+                log_line(log, line.decode("utf-8", errors="replace").rstrip())
+
+        reader = threading.Thread(target=read_decoded, daemon=True)
+        reader.start()
+        while not stop_event.is_set():
+            data = ser.read(4096)
+            if data:
+                capture.write(data)
+                decoder.stdin.write(data)
+                decoder.stdin.flush()
+            else:
+                time.sleep(0.01)
+        decoder.stdin.close()
+        decoder.wait()
+        reader.join()
+
 def main():
-    port = find_port()
+    arg_parser = argparse.ArgumentParser(description="Start the experiment and record its output")
+    arg_parser.add_argument("port", nargs="?", help="serial port, found automatically if omitted")
+    arg_parser.add_argument("--ecc", help="ECC scalar multiplication: double-and-add, ladder or wnaf:<2-6>")
+    arg_parser.add_argument("--comb", action="store_true", help="compute k*G with the generator comb from the table")
+    arg_parser.add_argument("--rsa", help="RSA decryption: plain, crt or crt-checked")
+    arg_parser.add_argument(
+        "--rsa-exp", help="RSA exponentiation: square-and-multiply, k-ary:<1-6>, sliding:<1-6>, fixed:<1-6> or builtin"
+    )
+    arg_parser.add_argument("--raw", action="store_true", help="also print every iteration's cycles, saved to samples.csv")
+    arg_parser.add_argument("--summary", action="store_true", help="also print min/max/median/stddev of each trial")
+    arg_parser.add_argument(
+        "--binary", action="store_true", help="receive checksummed frames, decoded by ee-decoder, instead of text"
+    )
+    arg_parser.add_argument("--iterations", type=int, default=15, help="iterations per trial, 1 to 64 (default 15)")
+    arg_parser.add_argument(
+        "--rows", default="all", help="table rows to measure: all (default) or a comma-separated list such as 0,4,7"
+    )
+    arg_parser.add_argument("--algorithms", choices=["all", "ecc", "rsa"], default="all", help="measure only ECC or only RSA")
+    args = arg_parser.parse_args()
+
+    port = args.port or find_port()
     if port is None:
-        if len(sys.argv) > 1:
-            port = sys.argv[1]
-        else:
-            sys.exit(1)
+        sys.exit(1)
 
-    if len(sys.argv) > 1:
-        port = sys.argv[1]
+    # the firmware's build defaults apply to anything not given here
+    start = " ".join(
+        ["START"]
+        + ([args.ecc] if args.ecc else [])
+        + (["comb"] if args.comb else [])
+        + ([args.rsa] if args.rsa else [])
+        + ([args.rsa_exp] if args.rsa_exp else [])
+        + (["raw"] if args.raw else [])
+        + (["summary"] if args.summary else [])
+    )
+    # SET and SELECT stay in effect on the device between runs, so all of them are sent every time
+    commands = [
+        f"SET output {'binary' if args.binary else 'text'}",
+        f"SET iterations {args.iterations}",
+        f"SELECT rows {args.rows}",
+        f"SELECT algorithms {args.algorithms}",
+        start,
+    ]
 
     log_path = os.path.join(LOG_DIR, "data.log")
 
     print(f"pid={os.getpid()}", flush=True)
 
-    t = threading.Thread(target=monitor, args=(port, log_path), daemon=True)
+    t = threading.Thread(target=monitor, args=(port, log_path, commands, args.binary), daemon=True)
     t.start()
 
     try:
@@ -1,11 +1,33 @@
 import re
 
+# the per-trial phase breakdown, in the order the firmware prints it
+PHASES = ["ECC setup", "ECC public keys", "ECC shared secret", "RSA exponent", "RSA encryption", "RSA decryption"]
+# the summary of a trial's iterations, printed with START ... summary
+SUMMARY = [f"{algorithm} {stat}" for algorithm in ("ECC", "RSA") for stat in ("min", "max", "median", "stddev")]
+
+def empty_trial():
+    return {
+        "ECC": None,
+        "RSA": None,
+        "ECC fails": None,
+        "RSA fails": None,
+        "ECC us": None,
+        "RSA us": None,
+        "ECC keygen": None,
+        "RSA keygen": None,
+        **{phase: None for phase in PHASES},
+        "ECC interrupts": None,
+        "RSA interrupts": None,
+    }
+
 def parse(log_file: str):
     import csv
     with open(log_file, "r") as file:
         lines = "".join(file.readlines()[2:])
 
     blocks = {}
+    # [ECC bits, trial, iteration, ECC, RSA] per iteration, printed with START ... raw
+    samples = []
     for match in re.finditer(r"=== ECC (\d+) / RSA \d+ bits ===\n(.*?)(?:\n\n|\Z)", lines, re.DOTALL):
         trials = []
         bit_size = int(match.group(1))
@@ -13,22 +35,49 @@ def parse(log_file: str):
 
         for trial in block_content.split("\n"):
             trial = trial.strip()
-            data = re.match(r"Trial #\d{1,3}: ECC = (\d+), RSA = (\d+), ECC fails = (\d+), RSA fails = (\d+)", trial)
+            # failed exchanges and unreliable timings are reported on lines of their own
+            if trial.startswith("[ERROR]"):
+                print(f"{bit_size} bits: {trial}")
+                continue
+            # replies to STATUS or PING sent during the run
+            if trial.startswith("OK ") or trial.startswith("ERR "):
+                continue
+            sample = re.match(r"Sample \d+/(\d+)/(\d+): ECC = (\d+), RSA = (\d+)", trial)
+            if sample:
+                samples.append([bit_size] + [int(value) for value in sample.groups()])
+                continue
+            summary = re.match(r"Summary #\d{1,3}: ECC min/max/median/stddev = (\d+)/(\d+)/(\d+)/(\d+), RSA min/max/median/stddev = (\d+)/(\d+)/(\d+)/(\d+)", trial)
+            if summary:
+                # follows its trial's line
+                if trials:
+                    trials[-1].update({stat: int(value) for stat, value in zip(SUMMARY, summary.groups())})
+                continue
+            data = re.match(r"Trial #(?P<trial>\d{1,3}): ECC = (\d+), RSA = (\d+), ECC fails = (\d+), RSA fails = (\d+)(?:, ECC us = (\d+), RSA us = (\d+))?(?:, ECC keygen = (\d+), RSA keygen = (\d+))?(?:, ECC phases = (\d+)/(\d+)/(\d+), RSA phases = (\d+)/(\d+)/(\d+))?(?:, ECC interrupts = (?P<ecc_interrupts>\d+), RSA interrupts = (?P<rsa_interrupts>\d+))?", trial)
             if not data:
                 print(f"Error parsing trial: {bit_size} bits, Trial #{len(trials) + 1}")
-                trials.append({
-                    "ECC": None,
-                    "RSA": None,
-                    "ECC fails": None,
-                    "RSA fails": None
-                })
+                trials.append(empty_trial())
                 continue
+            # trials whose frames ee-decoder dropped stay empty instead of shifting the rest
+            while len(trials) + 1 < int(data.group("trial")):
+                print(f"Missing trial: {bit_size} bits, Trial #{len(trials) + 1}")
+                trials.append(empty_trial())
 
             trials.append({
-                "ECC": int(data.group(1)),
-                "RSA": int(data.group(2)),
-                "ECC fails": int(data.group(3)),
-                "RSA fails": int(data.group(4))
+                "ECC": int(data.group(2)),
+                "RSA": int(data.group(3)),
+                "ECC fails": int(data.group(4)),
+                "RSA fails": int(data.group(5)),
+                # not printed by firmware from before the 64-bit clock
+                "ECC us": int(data.group(6)) if data.group(6) else None,
+                "RSA us": int(data.group(7)) if data.group(7) else None,
+                # only printed by firmware built with the keygen-timing feature
+                "ECC keygen": int(data.group(8)) if data.group(8) else None,
+                "RSA keygen": int(data.group(9)) if data.group(9) else None,
+                # not printed by firmware from before the phase breakdown
+                **{phase: int(data.group(10 + i)) if data.group(10 + i) else None for i, phase in enumerate(PHASES)},
+                # not printed by firmware from before USB moved to core 1
+                "ECC interrupts": int(data.group("ecc_interrupts")) if data.group("ecc_interrupts") else None,
+                "RSA interrupts": int(data.group("rsa_interrupts")) if data.group("rsa_interrupts") else None,
             })
 
         blocks[bit_size] = trials
@@ -52,10 +101,19 @@ def parse(log_file: str):
         def value_or_dash(value):
             return "-" if value is None or value == "" else value
 
-        for algorithm, time_key in [
-            ("RSA", "RSA"),
-            ("ECC", "ECC"),
-        ]:
+        algorithms = [("RSA", "RSA"), ("ECC", "ECC")]
+        if any(trial["RSA us"] is not None for trials in blocks.values() for trial in trials):
+            algorithms += [("RSA us", "RSA us"), ("ECC us", "ECC us")]
+        if any(trial["RSA keygen"] is not None for trials in blocks.values() for trial in trials):
+            algorithms += [("RSA keygen", "RSA keygen"), ("ECC keygen", "ECC keygen")]
+        if any(trial["ECC setup"] is not None for trials in blocks.values() for trial in trials):
+            algorithms += [(phase, phase) for phase in PHASES]
+        if any(trial["RSA interrupts"] is not None for trials in blocks.values() for trial in trials):
+            algorithms += [("RSA interrupts", "RSA interrupts"), ("ECC interrupts", "ECC interrupts")]
+        if any("ECC min" in trial for trials in blocks.values() for trial in trials):
+            algorithms += [(stat, stat) for stat in SUMMARY]
+
+        for algorithm, time_key in algorithms:
             for trial_idx in range(num_trials):
                 row = [algorithm, trial_idx + 1]
                 for bit_size in bit_sizes:
@@ -72,5 +130,12 @@ def parse(log_file: str):
 
     print("CSV file 'results.csv' written.")
 
+    if samples:
+        with open("samples.csv", "w", newline="") as csvfile:
+            writer = csv.writer(csvfile)
+            writer.writerow(["ECC bits", "Trial #", "Iteration", "ECC", "RSA"])
+            writer.writerows(samples)
+        print("CSV file 'samples.csv' written.")
+
 if __name__ == "__main__":
     parse("./data.log")
\ No newline at end of file
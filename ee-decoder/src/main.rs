@@ -0,0 +1,79 @@
+use clap::Parser;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use ee_protocol::{decode, MAX_FRAME};
+
+// turns the framed output of `SET output binary` back into the text the firmware prints otherwise,
+// so data-receiver/parser.py reads either
+#[derive(Parser, Debug)]
+#[command(version, about = "Decode the experiment's framed binary output into its text log")]
+struct Cli {
+    /// Raw bytes captured from the serial port, stdin if omitted
+    input: Option<PathBuf>,
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let input: Box<dyn Read> = match &cli.input {
+        Some(path) => match File::open(path) {
+            Ok(file) => Box::new(file),
+            Err(e) => {
+                eprintln!("Could not open {}: {}", path.display(), e);
+                return ExitCode::FAILURE;
+            }
+        },
+        None => Box::new(io::stdin()),
+    };
+    let mut input = BufReader::new(input);
+    let mut stdout = io::stdout().lock();
+
+    let (mut frames, mut corrupt) = (0usize, 0usize);
+    let mut frame = Vec::with_capacity(MAX_FRAME);
+    loop {
+        frame.clear();
+        match input.read_until(0, &mut frame) {
+            Ok(0) => break,
+            Ok(_) => {}
+            Err(e) => {
+                eprintln!("Read failed: {}", e);
+                return ExitCode::FAILURE;
+            }
+        }
+        // the input ended without closing the frame
+        let complete = frame.last() == Some(&0);
+        if complete {
+            frame.pop();
+        }
+        if frame.is_empty() {
+            continue;
+        }
+        frames += 1;
+
+        // a corrupt frame is reported where it was lost, in the log itself
+        let line = if !complete {
+            corrupt += 1;
+            format!("[ERROR] Frame #{} dropped: cut off at the end of the input\n", frames)
+        } else if frame.len() > MAX_FRAME {
+            corrupt += 1;
+            format!("[ERROR] Frame #{} dropped: {} bytes, longer than any message\n", frames, frame.len())
+        } else {
+            match decode(&mut frame) {
+                Ok(message) => message.to_string(),
+                Err(e) => {
+                    corrupt += 1;
+                    format!("[ERROR] Frame #{} dropped: {}\n", frames, e)
+                }
+            }
+        };
+        // flushed per message, the data receiver reads the log as it is decoded
+        if stdout.write_all(line.as_bytes()).and_then(|_| stdout.flush()).is_err() {
+            break;
+        }
+    }
+
+    eprintln!("{} frames, {} corrupt", frames, corrupt);
+    if corrupt > 0 { ExitCode::FAILURE } else { ExitCode::SUCCESS }
+}
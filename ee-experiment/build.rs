@@ -21,6 +21,7 @@ fn main() {
     }
 
     println!("cargo::rustc-check-cfg=cfg(rp2350)");
+    println!("cargo::rustc-check-cfg=cfg(host_test)");
 
     // Put the linker script somewhere the linker can find it
     let out = PathBuf::from(std::env::var_os("OUT_DIR").unwrap());
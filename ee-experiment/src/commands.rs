@@ -0,0 +1,121 @@
+use core::fmt;
+
+use crate::TRIALS_PER_KEY;
+
+// one command per line, every line is answered with "OK <line>[ detail]" or "ERR <line>: reason"
+//   PING
+//   INFO                                   settings and parameter table
+//   SET iterations <1-64>                  iterations per trial, TRIALS_PER_KEY until set
+//   SET output <text | binary>             binary sends everything as ee-protocol frames
+//   SELECT rows <all | i,j,...>            table rows the next runs measure
+//   SELECT algorithms <all | ecc | rsa>
+//   START [options]                        see parse_start
+//   ABORT                                  ends a run after its current iteration
+//   STATUS                                 idle, or the row, trial and iteration being run
+// SET and SELECT stay in effect for every later run, START's options only for its own
+
+// the sample arrays are this long, on the stack
+pub const MAX_ITERATIONS: usize = 64;
+
+pub enum Command<'a> {
+    Ping,
+    Info,
+    SetIterations(usize),
+    SetOutput { binary: bool },
+    SelectRows(Rows),
+    SelectAlgorithms { ecc: bool, rsa: bool },
+    // the options after START
+    Start(&'a str),
+    Abort,
+    Status,
+}
+
+// table rows as a bit set, bit i for row i
+#[derive(Clone, Copy, PartialEq)]
+pub struct Rows(u32);
+
+impl Rows {
+    pub const ALL: Rows = Rows(u32::MAX);
+    // rows past this can't be selected one by one
+    pub const MAX: usize = 32;
+
+    pub fn contains(&self, row: usize) -> bool {
+        row < Self::MAX && self.0 >> row & 1 == 1
+    }
+
+    // highest row selected one by one, None for all
+    pub fn last(&self) -> Option<usize> {
+        if *self == Rows::ALL { None } else { Some(Self::MAX - 1 - self.0.leading_zeros() as usize) }
+    }
+}
+
+impl fmt::Display for Rows {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if *self == Rows::ALL {
+            return write!(f, "all");
+        }
+        let mut rows = (0..Self::MAX).filter(|&row| self.contains(row));
+        if let Some(first) = rows.next() {
+            write!(f, "{}", first)?;
+        }
+        rows.try_for_each(|row| write!(f, ",{}", row))
+    }
+}
+
+// what SET and SELECT changed
+pub struct Settings {
+    pub iterations: usize,
+    pub rows: Rows,
+    pub ecc: bool,
+    pub rsa: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings { iterations: TRIALS_PER_KEY, rows: Rows::ALL, ecc: true, rsa: true }
+    }
+}
+
+// the reason goes after "ERR <line>: "
+pub fn parse(line: &str) -> Result<Command<'_>, &'static str> {
+    let (command, rest) = line.split_once(' ').unwrap_or((line, ""));
+    let rest = rest.trim();
+    let mut words = rest.split_ascii_whitespace();
+    let only = |command| if rest.is_empty() { Ok(command) } else { Err("takes no arguments") };
+    match command {
+        "PING" => only(Command::Ping),
+        "INFO" => only(Command::Info),
+        "ABORT" => only(Command::Abort),
+        "STATUS" => only(Command::Status),
+        "START" => Ok(Command::Start(rest)),
+        "SET" => match (words.next(), words.next(), words.next()) {
+            (Some("iterations"), Some(count), None) => match count.parse() {
+                Ok(count) if (1..=MAX_ITERATIONS).contains(&count) => Ok(Command::SetIterations(count)),
+                _ => Err("iterations must be 1 to 64"),
+            },
+            (Some("output"), Some("text"), None) => Ok(Command::SetOutput { binary: false }),
+            (Some("output"), Some("binary"), None) => Ok(Command::SetOutput { binary: true }),
+            (Some("output"), _, _) => Err("output must be text or binary"),
+            _ => Err("expected SET iterations <n> or SET output <text | binary>"),
+        },
+        "SELECT" => match (words.next(), words.next(), words.next()) {
+            (Some("rows"), Some(rows), None) => parse_rows(rows).map(Command::SelectRows),
+            (Some("algorithms"), Some("all"), None) => Ok(Command::SelectAlgorithms { ecc: true, rsa: true }),
+            (Some("algorithms"), Some("ecc"), None) => Ok(Command::SelectAlgorithms { ecc: true, rsa: false }),
+            (Some("algorithms"), Some("rsa"), None) => Ok(Command::SelectAlgorithms { ecc: false, rsa: true }),
+            (Some("algorithms"), _, _) => Err("algorithms must be all, ecc or rsa"),
+            _ => Err("expected SELECT rows <all | i,j,...> or SELECT algorithms <all | ecc | rsa>"),
+        },
+        _ => Err("unknown command"),
+    }
+}
+
+fn parse_rows(rows: &str) -> Result<Rows, &'static str> {
+    if rows == "all" {
+        return Ok(Rows::ALL);
+    }
+    rows.split(',').try_fold(Rows(0), |selected, row| match row.parse::<usize>() {
+        Ok(row) if row < Rows::MAX => Ok(Rows(selected.0 | 1 << row)),
+        _ => Err("rows must be all or a comma-separated list of row numbers"),
+    })
+}
@@ -1,40 +1,144 @@
-use crypto_bigint::{U256, Limb};
+use core::str::FromStr;
+
+use crypto_bigint::{Uint, Limb, Word};
 use crypto_bigint::modular::runtime_mod::{DynResidue, DynResidueParams};
+use crypto_bigint::subtle::{Choice, ConditionallySelectable, ConstantTimeEq};
+
+use crate::lookup::{ECC, EccCurve, Point};
+use crate::timing::{Clock, PhaseTimer};
+
+// widest ECC row, U256, bounds the wNAF digit buffer
+const MAX_SCALAR_BITS: usize = 256;
+// wNAF keeps 2^(w - 2) odd multiples on the stack
+pub const MAX_WNAF_WIDTH: u8 = 6;
+// matches primegen's --comb-teeth limit
+pub const MAX_COMB_TEETH: usize = 6;
+const MAX_COMB_POINTS: usize = (1 << MAX_COMB_TEETH) - 1;
+
+// point representation used inside scalar multiplication
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Coordinates {
+    // (x, y), one inversion per addition and doubling
+    Affine,
+    // (X, Y, Z) for (X/Z^2, Y/Z^3), inversion-free, converted back to affine once at the end
+    Jacobian,
+}
+
+// how the scalar is walked
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScalarMult {
+    // right-to-left double-and-add, branches on every key bit
+    DoubleAndAdd,
+    // Montgomery ladder on Jacobian points, one addition and one doubling per bit of the order
+    // whatever the key is, conditional swaps instead of branches
+    Ladder,
+    // width-w NAF on Jacobian points with 2^(w - 2) precomputed odd multiples, w from 2 to 6
+    Wnaf(u8),
+}
+
+impl ScalarMult {
+    // only double-and-add has an affine version, the others run on Jacobian points whatever the
+    // build asked for
+    pub fn coordinates(self, requested: Coordinates) -> Coordinates {
+        match self {
+            ScalarMult::DoubleAndAdd => requested,
+            ScalarMult::Ladder | ScalarMult::Wnaf(_) => Coordinates::Jacobian,
+        }
+    }
+}
+
+// the names the host sends after START: double-and-add, ladder or wnaf:<width>
+impl FromStr for ScalarMult {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s {
+            "double-and-add" => Ok(ScalarMult::DoubleAndAdd),
+            "ladder" => Ok(ScalarMult::Ladder),
+            _ => {
+                let width: u8 = s.strip_prefix("wnaf:").ok_or(())?.parse().map_err(|_| ())?;
+                if (2..=MAX_WNAF_WIDTH).contains(&width) { Ok(ScalarMult::Wnaf(width)) } else { Err(()) }
+            }
+        }
+    }
+}
 
-use crate::lookup::ECC;
+// generator comb from primegen's table, entry i - 1 is the sum of 2^(j * spacing) G over the set
+// bits j of i, kept in Montgomery form so the timed window only copies coordinates
+pub struct Comb<const E: usize> {
+    points: [Point<E>; MAX_COMB_POINTS],
+    teeth: usize,
+    spacing: usize,
+}
 
-type Params256 = DynResidueParams<{ U256::LIMBS }>;
-type Residue256 = DynResidue<{ U256::LIMBS }>;
+impl<const E: usize> Comb<E> {
+    // None without a comb in the table or if one of its points is missing
+    pub fn new(curve: &EccCurve<E>, teeth: usize, point: impl Fn(usize) -> Option<Point<E>>) -> Option<Self> {
+        if teeth == 0 || teeth > MAX_COMB_TEETH {
+            return None;
+        }
+        let params = DynResidueParams::new(&curve.p);
+        let mut points = [Point { x: Uint::ZERO, y: Uint::ZERO }; MAX_COMB_POINTS];
+        for (idx, slot) in points.iter_mut().take((1 << teeth) - 1).enumerate() {
+            let p = point(idx)?;
+            *slot = Point {
+                x: *DynResidue::new(&p.x, params).as_montgomery(),
+                y: *DynResidue::new(&p.y, params).as_montgomery(),
+            };
+        }
+        Some(Comb { points, teeth, spacing: bitlen_vt(&curve.order).div_ceil(teeth) })
+    }
+}
 
-// precomputed modular arithmetic params for a curve's prime field
+// precomputed modular arithmetic params for a curve's prime field, E limbs wide
 #[derive(Clone, Copy)]
-pub struct EccCtx {
-    pub params: Params256,
-    a: Residue256,
-    two: Residue256,
-    three: Residue256,
+pub struct EccCtx<'a, const E: usize> {
+    pub params: DynResidueParams<E>,
+    coordinates: Coordinates,
+    scalar_mult: ScalarMult,
+    // k * G goes through the comb when there is one
+    comb: Option<&'a Comb<E>>,
+    order: Uint<E>,
+    order_bits: usize,
+    a: DynResidue<E>,
+    // a = -3 mod p allows the cheaper Jacobian doubling
+    a_is_minus_3: bool,
+    zero: DynResidue<E>,
+    one: DynResidue<E>,
+    two: DynResidue<E>,
+    three: DynResidue<E>,
 }
 
-impl EccCtx {
-    pub fn new(p: U256, a: U256) -> Self {
-        let params = Params256::new(&p);
+impl<'a, const E: usize> EccCtx<'a, E> {
+    pub fn new(curve: &EccCurve<E>, coordinates: Coordinates, scalar_mult: ScalarMult, comb: Option<&'a Comb<E>>) -> Self {
+        let params = DynResidueParams::new(&curve.p);
+        let a = DynResidue::new(&curve.a, params);
+        let three = DynResidue::new(&Uint::from(3u64), params);
         EccCtx {
             params,
-            a: DynResidue::new(&a, params),
-            two: DynResidue::new(&U256::from(2u64), params),
-            three: DynResidue::new(&U256::from(3u64), params),
+            coordinates,
+            scalar_mult,
+            comb,
+            order: curve.order,
+            order_bits: bitlen_vt(&curve.order),
+            a,
+            a_is_minus_3: a == -three,
+            zero: DynResidue::zero(params),
+            one: DynResidue::one(params),
+            two: DynResidue::new(&Uint::from(2u64), params),
+            three,
         }
     }
 }
 
 #[derive(Clone, Copy)]
-struct ResiduePoint {
-    x: Residue256,
-    y: Residue256,
+struct ResiduePoint<const E: usize> {
+    x: DynResidue<E>,
+    y: DynResidue<E>,
     inf: bool,
 }
 
-fn point_add(p1: &ResiduePoint, p2: &ResiduePoint, ctx: &EccCtx) -> ResiduePoint {
+fn point_add<const E: usize>(p1: &ResiduePoint<E>, p2: &ResiduePoint<E>, ctx: &EccCtx<E>) -> ResiduePoint<E> {
     if p1.inf { return *p2; }
     if p2.inf { return *p1; }
 
@@ -71,16 +175,16 @@ fn point_add(p1: &ResiduePoint, p2: &ResiduePoint, ctx: &EccCtx) -> ResiduePoint
 }
 
 #[inline(always)]
-fn bit_vt(val: &U256, bit: usize) -> bool {
+fn bit_vt<const E: usize>(val: &Uint<E>, bit: usize) -> bool {
     let li = bit / Limb::BITS;
     let bi = bit % Limb::BITS;
     (val.as_limbs()[li].0 >> bi) & 1 == 1
 }
 
 #[inline(always)]
-fn bitlen_vt(val: &U256) -> usize {
+fn bitlen_vt<const E: usize>(val: &Uint<E>) -> usize {
     let limbs = val.as_limbs();
-    let mut i = U256::LIMBS;
+    let mut i = E;
     while i > 0 {
         i -= 1;
         let w = limbs[i].0;
@@ -91,8 +195,304 @@ fn bitlen_vt(val: &U256) -> usize {
     0
 }
 
+// Jacobian point, Z = 0 is the point at infinity
+#[derive(Clone, Copy)]
+struct JacobianPoint<const E: usize> {
+    x: DynResidue<E>,
+    y: DynResidue<E>,
+    z: DynResidue<E>,
+}
+
+impl<const E: usize> JacobianPoint<E> {
+    fn infinity(ctx: &EccCtx<E>) -> Self {
+        JacobianPoint { x: ctx.one, y: ctx.one, z: ctx.zero }
+    }
+
+    fn from_affine(p: &ResiduePoint<E>, ctx: &EccCtx<E>) -> Self {
+        if p.inf {
+            return Self::infinity(ctx);
+        }
+        JacobianPoint { x: p.x, y: p.y, z: ctx.one }
+    }
+
+    fn is_infinity(&self, ctx: &EccCtx<E>) -> bool {
+        self.z == ctx.zero
+    }
+
+    fn ct_is_infinity(&self, ctx: &EccCtx<E>) -> Choice {
+        self.z.ct_eq(&ctx.zero)
+    }
+
+    // the only inversion of a Jacobian scalar multiplication
+    fn to_affine(self, ctx: &EccCtx<E>) -> ResiduePoint<E> {
+        if self.is_infinity(ctx) {
+            return ResiduePoint { x: ctx.zero, y: ctx.zero, inf: true };
+        }
+        let (z_inv, _) = self.z.invert();
+        let z_inv2 = z_inv.square();
+        ResiduePoint { x: self.x * z_inv2, y: self.y * z_inv2 * z_inv, inf: false }
+    }
+}
+
+impl<const E: usize> ConditionallySelectable for JacobianPoint<E> {
+    fn conditional_select(a: &Self, b: &Self, choice: Choice) -> Self {
+        JacobianPoint {
+            x: DynResidue::conditional_select(&a.x, &b.x, choice),
+            y: DynResidue::conditional_select(&a.y, &b.y, choice),
+            z: DynResidue::conditional_select(&a.z, &b.z, choice),
+        }
+    }
+}
+
+fn jacobian_double<const E: usize>(p: &JacobianPoint<E>, ctx: &EccCtx<E>) -> JacobianPoint<E> {
+    if p.is_infinity(ctx) || p.y == ctx.zero {
+        return JacobianPoint::infinity(ctx);
+    }
+    jacobian_double_unchecked(p, ctx)
+}
+
+// doubling formulas from the Explicit-Formulas Database: dbl-2001-b for a = -3, dbl-2007-bl for
+// any other a, without the special cases (Z = 0 stays Z = 0, Y = 0 never happens in an odd order
+// subgroup)
+fn jacobian_double_unchecked<const E: usize>(p: &JacobianPoint<E>, ctx: &EccCtx<E>) -> JacobianPoint<E> {
+    if ctx.a_is_minus_3 {
+        // alpha = 3(X - Z^2)(X + Z^2), beta = X Y^2
+        let delta = p.z.square();
+        let gamma = p.y.square();
+        let beta = p.x * gamma;
+        let alpha = ctx.three * (p.x - delta) * (p.x + delta);
+        let beta4 = (beta + beta) + (beta + beta);
+        let gamma_sq = gamma.square();
+        let gamma_sq8 = gamma_sq + gamma_sq;
+        let gamma_sq8 = gamma_sq8 + gamma_sq8;
+        let gamma_sq8 = gamma_sq8 + gamma_sq8;
+
+        let x3 = alpha.square() - (beta4 + beta4);
+        let y3 = alpha * (beta4 - x3) - gamma_sq8;
+        let z3 = (p.y + p.z).square() - gamma - delta;
+        JacobianPoint { x: x3, y: y3, z: z3 }
+    } else {
+        // M = 3X^2 + aZ^4, S = 4XY^2
+        let xx = p.x.square();
+        let yy = p.y.square();
+        let yyyy = yy.square();
+        let zz = p.z.square();
+        let s = (p.x + yy).square() - xx - yyyy;
+        let s = s + s;
+        let m = xx + xx + xx + ctx.a * zz.square();
+        let yyyy8 = yyyy + yyyy;
+        let yyyy8 = yyyy8 + yyyy8;
+        let yyyy8 = yyyy8 + yyyy8;
+
+        let x3 = m.square() - (s + s);
+        let y3 = m * (s - x3) - yyyy8;
+        let z3 = (p.y + p.z).square() - yy - zz;
+        JacobianPoint { x: x3, y: y3, z: z3 }
+    }
+}
+
+// add-2007-bl, falls back to doubling for equal points
+fn jacobian_add<const E: usize>(p1: &JacobianPoint<E>, p2: &JacobianPoint<E>, ctx: &EccCtx<E>) -> JacobianPoint<E> {
+    if p1.is_infinity(ctx) { return *p2; }
+    if p2.is_infinity(ctx) { return *p1; }
+
+    let z1z1 = p1.z.square();
+    let z2z2 = p2.z.square();
+    let u1 = p1.x * z2z2;
+    let u2 = p2.x * z1z1;
+    let s1 = p1.y * p2.z * z2z2;
+    let s2 = p2.y * p1.z * z1z1;
+    let h = u2 - u1;
+    let r = s2 - s1;
+
+    if h == ctx.zero {
+        // same x: either the same point or inverses
+        return if r == ctx.zero { jacobian_double(p1, ctx) } else { JacobianPoint::infinity(ctx) };
+    }
+    let z1z2 = (p1.z + p2.z).square() - z1z1 - z2z2;
+    add_from_differences(u1, s1, h, r, z1z2)
+}
+
+// add-2007-bl without branches, inverse points come out as Z = 0 on their own, equal points and
+// infinite inputs do not and have to be ruled out or selected around by the caller
+fn jacobian_add_unchecked<const E: usize>(p1: &JacobianPoint<E>, p2: &JacobianPoint<E>) -> JacobianPoint<E> {
+    let z1z1 = p1.z.square();
+    let z2z2 = p2.z.square();
+    let u1 = p1.x * z2z2;
+    let u2 = p2.x * z1z1;
+    let s1 = p1.y * p2.z * z2z2;
+    let s2 = p2.y * p1.z * z1z1;
+    let z1z2 = (p1.z + p2.z).square() - z1z1 - z2z2;
+    add_from_differences(u1, s1, u2 - u1, s2 - s1, z1z2)
+}
+
+// rest of add-2007-bl once h = U2 - U1 and r = S2 - S1 are known, z1z2 is 2 Z1 Z2
+#[inline(always)]
+fn add_from_differences<const E: usize>(
+    u1: DynResidue<E>,
+    s1: DynResidue<E>,
+    h: DynResidue<E>,
+    r: DynResidue<E>,
+    z1z2: DynResidue<E>,
+) -> JacobianPoint<E> {
+    let i = (h + h).square();
+    let j = h * i;
+    let r = r + r;
+    let v = u1 * i;
+    let s1j = s1 * j;
+
+    let x3 = r.square() - j - (v + v);
+    let y3 = r * (v - x3) - (s1j + s1j);
+    let z3 = z1z2 * h;
+    JacobianPoint { x: x3, y: y3, z: z3 }
+}
+
+fn scalar_mult<const E: usize>(k: Uint<E>, point: &ResiduePoint<E>, ctx: &EccCtx<E>) -> ResiduePoint<E> {
+    match (ctx.scalar_mult, ctx.coordinates) {
+        (ScalarMult::Ladder, _) => scalar_mult_ladder(k, point, ctx).to_affine(ctx),
+        (ScalarMult::Wnaf(width), _) => scalar_mult_wnaf(k, point, width, ctx).to_affine(ctx),
+        (ScalarMult::DoubleAndAdd, Coordinates::Affine) => scalar_mult_affine(k, point, ctx),
+        (ScalarMult::DoubleAndAdd, Coordinates::Jacobian) => scalar_mult_jacobian(k, point, ctx).to_affine(ctx),
+    }
+}
+
+// bit i of a scalar that can be one bit wider than Uint<E>, the extra bit lives in carry
+#[inline(always)]
+fn padded_bit<const E: usize>(low: &Uint<E>, carry: Limb, bit: usize) -> Word {
+    if bit == Uint::<E>::BITS {
+        return carry.0 & 1;
+    }
+    (low.as_limbs()[bit / Limb::BITS].0 >> (bit % Limb::BITS)) & 1
+}
+
+// constant-time Montgomery ladder, k is padded to k + n or k + 2n so its top bit always sits at
+// order_bits and the loop length does not depend on the key, R1 - R0 = P throughout
+fn scalar_mult_ladder<const E: usize>(k: Uint<E>, point: &ResiduePoint<E>, ctx: &EccCtx<E>) -> JacobianPoint<E> {
+    let (k1, c1) = k.adc(&ctx.order, Limb::ZERO);
+    let (k2, c2) = k1.adc(&ctx.order, Limb::ZERO);
+    let c2 = c1.wrapping_add(c2);
+    let wide = Choice::from(padded_bit(&k1, c1, ctx.order_bits) as u8);
+    let k = Uint::conditional_select(&k2, &k1, wide);
+    let carry = Limb::conditional_select(&c2, &c1, wide);
+
+    let p = JacobianPoint::from_affine(point, ctx);
+    let mut r0 = p;
+    let mut r1 = jacobian_double_unchecked(&p, ctx);
+
+    for i in (0..ctx.order_bits).rev() {
+        let bit = Choice::from(padded_bit(&k, carry, i) as u8);
+        JacobianPoint::conditional_swap(&mut r0, &mut r1, bit);
+        // R0 or R1 is infinity when a prefix of k hits n - 1 or n, the formulas would zero the sum
+        let sum = jacobian_add_unchecked(&r0, &r1);
+        let sum = JacobianPoint::conditional_select(&sum, &r0, r1.ct_is_infinity(ctx));
+        r1 = JacobianPoint::conditional_select(&sum, &r1, r0.ct_is_infinity(ctx));
+        r0 = jacobian_double_unchecked(&r0, ctx);
+        JacobianPoint::conditional_swap(&mut r0, &mut r1, bit);
+    }
+    r0
+}
+
+// width-w NAF of k, least significant digit first: every non-zero digit is odd, below 2^(w - 1)
+// in magnitude and followed by at least w - 1 zeros. Returns the digit count
+fn wnaf<const E: usize>(k: Uint<E>, width: u8, digits: &mut [i8; MAX_SCALAR_BITS + 1]) -> usize {
+    let window = 1i16 << width;
+    let mut k = k;
+    let mut len = 0;
+    // bit Uint::<E>::BITS of k, set when subtracting a negative digit carried out of the top
+    let mut carry = false;
+    while carry || k != Uint::ZERO {
+        let mut digit = 0i16;
+        if bit_vt(&k, 0) {
+            digit = (k.as_limbs()[0].0 & (window - 1) as Word) as i16;
+            if digit >= window / 2 {
+                digit -= window;
+            }
+            if digit > 0 {
+                k = k.wrapping_sub(&Uint::from(digit as u8));
+            } else {
+                let (sum, c) = k.adc(&Uint::from((-digit) as u8), Limb::ZERO);
+                k = sum;
+                carry = c.0 != 0;
+            }
+        }
+        digits[len] = digit as i8;
+        len += 1;
+        k = k.shr_vartime(1);
+        if carry {
+            k = k.bitor(&Uint::ONE.shl_vartime(Uint::<E>::BITS - 1));
+            carry = false;
+        }
+    }
+    len
+}
+
+// left-to-right wNAF, a doubling per digit and an addition per non-zero digit
+fn scalar_mult_wnaf<const E: usize>(k: Uint<E>, point: &ResiduePoint<E>, width: u8, ctx: &EccCtx<E>) -> JacobianPoint<E> {
+    // P, 3P, 5P, ..., (2^(w - 1) - 1)P
+    let mut odd = [JacobianPoint::infinity(ctx); 1 << (MAX_WNAF_WIDTH - 2)];
+    odd[0] = JacobianPoint::from_affine(point, ctx);
+    let twice = jacobian_double(&odd[0], ctx);
+    for i in 1..1 << (width - 2) {
+        odd[i] = jacobian_add(&odd[i - 1], &twice, ctx);
+    }
+
+    let mut digits = [0i8; MAX_SCALAR_BITS + 1];
+    let len = wnaf(k, width, &mut digits);
+
+    let mut result = JacobianPoint::infinity(ctx);
+    for &digit in digits[..len].iter().rev() {
+        result = jacobian_double(&result, ctx);
+        if digit > 0 {
+            result = jacobian_add(&result, &odd[digit as usize / 2], ctx);
+        } else if digit < 0 {
+            let p = odd[(-digit) as usize / 2];
+            result = jacobian_add(&result, &JacobianPoint { y: -p.y, ..p }, ctx);
+        }
+    }
+    result
+}
+
+// fixed-base comb, one doubling per column and an addition whenever a tooth hits a set bit
+fn scalar_mult_comb<const E: usize>(k: Uint<E>, comb: &Comb<E>, ctx: &EccCtx<E>) -> JacobianPoint<E> {
+    let mut result = JacobianPoint::infinity(ctx);
+    for i in (0..comb.spacing).rev() {
+        result = jacobian_double(&result, ctx);
+        let mut idx = 0;
+        for j in 0..comb.teeth {
+            let bit = j * comb.spacing + i;
+            if bit < Uint::<E>::BITS && bit_vt(&k, bit) {
+                idx |= 1 << j;
+            }
+        }
+        if idx != 0 {
+            let p = &comb.points[idx - 1];
+            let point = JacobianPoint {
+                x: DynResidue::from_montgomery(p.x, ctx.params),
+                y: DynResidue::from_montgomery(p.y, ctx.params),
+                z: ctx.one,
+            };
+            result = jacobian_add(&result, &point, ctx);
+        }
+    }
+    result
+}
+
+// double-and-add in Jacobian coordinates, same bit order as the affine version
+fn scalar_mult_jacobian<const E: usize>(k: Uint<E>, point: &ResiduePoint<E>, ctx: &EccCtx<E>) -> JacobianPoint<E> {
+    let mut result = JacobianPoint::infinity(ctx);
+    let mut addend = JacobianPoint::from_affine(point, ctx);
+
+    for i in 0..bitlen_vt(&k) {
+        if bit_vt(&k, i) {
+            result = jacobian_add(&result, &addend, ctx);
+        }
+        addend = jacobian_double(&addend, ctx);
+    }
+    result
+}
+
 // double-and-add scalar multiplication
-fn scalar_mult(k: U256, point: &ResiduePoint, ctx: &EccCtx) -> ResiduePoint {
+fn scalar_mult_affine<const E: usize>(k: Uint<E>, point: &ResiduePoint<E>, ctx: &EccCtx<E>) -> ResiduePoint<E> {
     let bits = bitlen_vt(&k);
     if bits == 0 {
         return ResiduePoint {
@@ -118,16 +518,81 @@ fn scalar_mult(k: U256, point: &ResiduePoint, ctx: &EccCtx) -> ResiduePoint {
     result
 }
 
-pub fn ecdh(data: ECC, ctx: &EccCtx) -> bool {
-    let generator = ResiduePoint {
+fn generator<const E: usize>(data: &ECC<E>, ctx: &EccCtx<E>) -> ResiduePoint<E> {
+    ResiduePoint {
         x: DynResidue::new(&data.curve.generator.x, ctx.params),
         y: DynResidue::new(&data.curve.generator.y, ctx.params),
         inf: false,
-    };
+    }
+}
 
-    // Generate public keys
-    let pk1 = scalar_mult(data.private_key1, &generator, ctx);
-    let _ss2 = scalar_mult(data.private_key2, &pk1, ctx);
+// both parties' public keys, k1 * G and k2 * G, what key generation costs on the device
+#[derive(Clone, Copy)]
+pub struct PublicKeys<const E: usize> {
+    pk1: ResiduePoint<E>,
+    pk2: ResiduePoint<E>,
+}
 
-    true
-}
\ No newline at end of file
+// k * G, through the comb when the run has one
+fn base_mult<const E: usize>(k: Uint<E>, data: &ECC<E>, ctx: &EccCtx<E>) -> ResiduePoint<E> {
+    match ctx.comb {
+        Some(comb) => scalar_mult_comb(k, comb, ctx).to_affine(ctx),
+        None => scalar_mult(k, &generator(data, ctx), ctx),
+    }
+}
+
+pub fn keygen<const E: usize>(data: &ECC<E>, ctx: &EccCtx<E>) -> PublicKeys<E> {
+    PublicKeys {
+        pk1: base_mult(data.private_key1, data, ctx),
+        pk2: base_mult(data.private_key2, data, ctx),
+    }
+}
+
+// each party's shared secret, k1 * pk2 and k2 * pk1
+pub struct SharedSecrets<const E: usize> {
+    ss1: ResiduePoint<E>,
+    ss2: ResiduePoint<E>,
+}
+
+impl<const E: usize> SharedSecrets<E> {
+    // the exchange worked if both parties ended on the same finite point's x-coordinate
+    pub fn agree(&self) -> bool {
+        !self.ss1.inf && !self.ss2.inf && self.ss1.x.retrieve() == self.ss2.x.retrieve()
+    }
+}
+
+pub fn shared_secrets<const E: usize>(data: &ECC<E>, keys: &PublicKeys<E>, ctx: &EccCtx<E>) -> SharedSecrets<E> {
+    SharedSecrets {
+        ss1: scalar_mult(data.private_key1, &keys.pk2, ctx),
+        ss2: scalar_mult(data.private_key2, &keys.pk1, ctx),
+    }
+}
+
+// where ecdh's cycles go, indices into its PhaseTimer
+#[derive(Clone, Copy)]
+pub enum EccPhase {
+    // curve constants in Montgomery form
+    Setup,
+    // both public keys, k * G
+    Keygen,
+    // both shared secrets, k * pk
+    SharedSecret,
+}
+pub const ECC_PHASES: usize = 3;
+
+pub fn ecdh<const E: usize, C: Clock>(
+    data: &ECC<E>,
+    coordinates: Coordinates,
+    scalar_mult: ScalarMult,
+    comb: Option<&Comb<E>>,
+    timer: &mut PhaseTimer<C, ECC_PHASES>,
+) -> SharedSecrets<E> {
+    let ctx = EccCtx::new(&data.curve, coordinates, scalar_mult, comb);
+    timer.lap(EccPhase::Setup as usize);
+    // Generate public keys
+    let keys = keygen(data, &ctx);
+    timer.lap(EccPhase::Keygen as usize);
+    let secrets = shared_secrets(data, &keys, &ctx);
+    timer.lap(EccPhase::SharedSecret as usize);
+    secrets
+}
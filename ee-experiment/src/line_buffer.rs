@@ -0,0 +1,158 @@
+// received bytes in a ring, handed out one complete line at a time. A line ends at '\n' or a NUL
+// byte, a '\r' before the end is dropped and empty lines are skipped. A line that doesn't fit
+// is discarded whole, up to its end, and reported once instead of being cut into pieces
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Overflow {
+    // the length of the discarded line, without its end
+    pub dropped: usize,
+}
+
+pub struct LineBuffer<const N: usize> {
+    buf: [u8; N],
+    // oldest byte
+    start: usize,
+    len: usize,
+    // bytes of the unfinished line at the end of the ring
+    partial: usize,
+    // bytes of the unfinished line that didn't fit, 0 while it still does
+    dropped: usize,
+    // discarded lines not yet reported, their lengths added up
+    overflow: Option<Overflow>,
+}
+
+impl<const N: usize> LineBuffer<N> {
+    pub const fn new() -> Self {
+        LineBuffer { buf: [0; N], start: 0, len: 0, partial: 0, dropped: 0, overflow: None }
+    }
+
+    pub fn push(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            let end = byte == b'\n' || byte == 0;
+            if self.dropped == 0 && self.len < N {
+                self.buf[(self.start + self.len) % N] = byte;
+                self.len += 1;
+                self.partial = if end { 0 } else { self.partial + 1 };
+            } else if end {
+                self.discard_line();
+            } else {
+                self.dropped += 1;
+            }
+        }
+    }
+
+    // the unfinished line didn't fit, forget what was kept of it along with what wasn't
+    fn discard_line(&mut self) {
+        let dropped = self.partial + self.dropped;
+        self.len -= self.partial;
+        self.partial = 0;
+        self.dropped = 0;
+        // an empty line lost to a full ring is not worth reporting
+        if dropped > 0 {
+            let earlier = self.overflow.map_or(0, |overflow| overflow.dropped);
+            self.overflow = Some(Overflow { dropped: earlier + dropped });
+        }
+    }
+
+    // the next complete line into `out` without its end, a discarded line comes first
+    pub fn read_line(&mut self, out: &mut [u8; N]) -> Option<Result<usize, Overflow>> {
+        if let Some(overflow) = self.overflow.take() {
+            return Some(Err(overflow));
+        }
+        loop {
+            let complete = self.len - self.partial;
+            let line = (0..complete).find(|&i| matches!(self.buf[(self.start + i) % N], b'\n' | 0))?;
+            for (i, byte) in out[..line].iter_mut().enumerate() {
+                *byte = self.buf[(self.start + i) % N];
+            }
+            self.start = (self.start + line + 1) % N;
+            self.len -= line + 1;
+
+            let len = if line > 0 && out[line - 1] == b'\r' { line - 1 } else { line };
+            if len > 0 {
+                return Some(Ok(len));
+            }
+        }
+    }
+}
+
+#[cfg(all(test, host_test))]
+mod tests {
+    use super::*;
+
+    fn lines<const N: usize>(buffer: &mut LineBuffer<N>) -> Vec<Result<String, Overflow>> {
+        let mut out = [0; N];
+        let mut lines = Vec::new();
+        while let Some(line) = buffer.read_line(&mut out) {
+            lines.push(line.map(|len| String::from_utf8(out[..len].to_vec()).unwrap()));
+        }
+        lines
+    }
+
+    #[test]
+    fn line_split_across_pushes() {
+        let mut buffer = LineBuffer::<16>::new();
+        buffer.push(b"STA");
+        assert_eq!(lines(&mut buffer), []);
+        buffer.push(b"RT\r\nPI");
+        assert_eq!(lines(&mut buffer), [Ok("START".into())]);
+        buffer.push(b"NG\0");
+        assert_eq!(lines(&mut buffer), [Ok("PING".into())]);
+    }
+
+    #[test]
+    fn several_lines_and_empty_ones() {
+        let mut buffer = LineBuffer::<32>::new();
+        buffer.push(b"\n\r\nPING\n\0STATUS\nINFO");
+        assert_eq!(lines(&mut buffer), [Ok("PING".into()), Ok("STATUS".into())]);
+        buffer.push(b"\n");
+        assert_eq!(lines(&mut buffer), [Ok("INFO".into())]);
+    }
+
+    #[test]
+    fn wraps_around() {
+        let mut buffer = LineBuffer::<8>::new();
+        for _ in 0..10 {
+            buffer.push(b"ABORT\n");
+            assert_eq!(lines(&mut buffer), [Ok("ABORT".into())]);
+        }
+    }
+
+    #[test]
+    fn exactly_full() {
+        let mut buffer = LineBuffer::<8>::new();
+        buffer.push(b"1234567\n");
+        assert_eq!(lines(&mut buffer), [Ok("1234567".into())]);
+    }
+
+    #[test]
+    fn long_line_discarded_whole() {
+        let mut buffer = LineBuffer::<16>::new();
+        buffer.push(b"PING\nSELECT rows 1,2\nSTATUS\n");
+        assert_eq!(lines(&mut buffer), [Err(Overflow { dropped: 15 }), Ok("PING".into()), Ok("STATUS".into())]);
+    }
+
+    #[test]
+    fn end_of_line_not_fitting() {
+        let mut buffer = LineBuffer::<8>::new();
+        buffer.push(b"12345678\nPING\n");
+        assert_eq!(lines(&mut buffer), [Err(Overflow { dropped: 8 }), Ok("PING".into())]);
+    }
+
+    #[test]
+    fn unread_lines_fill_the_ring() {
+        let mut buffer = LineBuffer::<8>::new();
+        buffer.push(b"PING\nSTATUS\n");
+        assert_eq!(lines(&mut buffer), [Err(Overflow { dropped: 6 }), Ok("PING".into())]);
+        buffer.push(b"STATUS\n");
+        assert_eq!(lines(&mut buffer), [Ok("STATUS".into())]);
+    }
+
+    #[test]
+    fn overflows_add_up_until_read() {
+        let mut buffer = LineBuffer::<4>::new();
+        buffer.push(b"abcdef\nghijk\n");
+        assert_eq!(lines(&mut buffer), [Err(Overflow { dropped: 11 })]);
+        assert_eq!(lines(&mut buffer), []);
+    }
+}
@@ -1,15 +1,45 @@
+// USB belongs to core 1 (serve_usb), out of the way of the measurements on core 0, and is served
+// from its USBCTRL_IRQ. Core 0 hands it what to send through the SIO FIFO, as packets of a length
+// word followed by the bytes, four to a word, taken by core 1's SIO_IRQ_FIFO, and reads command
+// lines from INPUT
 use core::cell::RefCell;
 use core::fmt;
-use cortex_m::interrupt::Mutex;
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use cortex_m::asm;
+use critical_section::Mutex;
+use ee_protocol::{Message, MAX_FRAME};
+use crate::line_buffer::{LineBuffer, Overflow};
+use crate::tx_buffer::TxBuffer;
+#[cfg(not(host_test))]
+use cortex_m::peripheral::NVIC;
+#[cfg(not(host_test))]
+use rp235x_hal::pac::{interrupt, Interrupt};
+use rp235x_hal::sio::SioFifo;
 use rp235x_hal::usb::UsbBus;
+use usb_device::bus::UsbBusAllocator;
 use usb_device::prelude::*;
 use usbd_serial::SerialPort;
 const NAME: &str = "EE Experiment";
 
-static mut INPUT_BUFFER: [u8; 256] = [0; 256];
-static mut INPUT_POS: usize = 0;
+// received bytes until they make up a line, lines of up to LINE_LEN - 1 bytes fit. Shared by
+// both cores, so behind the spinlock of critical_section
+pub const LINE_LEN: usize = 256;
+static INPUT: Mutex<RefCell<LineBuffer<LINE_LEN>>> = Mutex::new(RefCell::new(LineBuffer::new()));
 
-pub static USB_SERIAL: Mutex<RefCell<Option<UsbSerial>>> = Mutex::new(RefCell::new(None));
+// core 1's queue to the host. Holds any one packet (a frame or a formatted piece of a line) whole
+const OUTPUT_LEN: usize = 1024;
+
+// core 0's end of the FIFO, set once core 1 runs
+static FIFO: cortex_m::interrupt::Mutex<RefCell<Option<SioFifo>>> = cortex_m::interrupt::Mutex::new(RefCell::new(None));
+
+// frames instead of text once SET output or START asked for it
+static BINARY: AtomicBool = AtomicBool::new(false);
+// bytes core 0 sent, and bytes core 1 passed on to USB. Each has one writer, so they need no
+// read-modify-write across the cores
+static QUEUED: AtomicU32 = AtomicU32::new(0);
+static DONE: AtomicU32 = AtomicU32::new(0);
+// packets that found the queue full and had to wait for the host
+static WAITS: AtomicU32 = AtomicU32::new(0);
 
 pub struct UsbSerial {
     pub serial: SerialPort<'static, UsbBus>,
@@ -22,14 +52,22 @@ impl UsbSerial {
     }
 }
 
-impl fmt::Write for UsbSerial {
+// what uprint! formats into
+pub struct Output;
+
+impl fmt::Write for Output {
     fn write_str(&mut self, s: &str) -> fmt::Result {
-        self.serial.write(s.as_bytes()).map_err(|_| fmt::Error)?;
+        write(s.as_bytes());
         Ok(())
     }
 }
 
-pub fn init_usb_serial(usb_bus: &'static usb_device::bus::UsbBusAllocator<UsbBus>) {
+// on core 0, with its end of the FIFO once core 1 is serving USB
+pub fn init(fifo: SioFifo) {
+    cortex_m::interrupt::free(|cs| FIFO.borrow(cs).replace(Some(fifo)));
+}
+
+fn init_usb_serial(usb_bus: &'static UsbBusAllocator<UsbBus>) -> UsbSerial {
     let serial = SerialPort::new(usb_bus);
 
     let usb_dev = UsbDeviceBuilder::new(usb_bus, UsbVidPid(0x16c0, 0x27dd))
@@ -41,74 +79,253 @@ pub fn init_usb_serial(usb_bus: &'static usb_device::bus::UsbBusAllocator<UsbBus
         .device_class(usbd_serial::USB_CLASS_CDC)
         .build();
 
-    cortex_m::interrupt::free(|cs| {
-        USB_SERIAL
-            .borrow(cs)
-            .replace(Some(UsbSerial { serial, usb_dev }));
-    });
+    UsbSerial { serial, usb_dev }
+}
+
+// a packet from core 0 on its way into the queue
+struct Incoming {
+    bytes: [u8; OUTPUT_LEN],
+    // None until the length word arrived
+    len: Option<usize>,
+    received: usize,
+}
+
+impl Incoming {
+    // takes words from the FIFO until the packet is complete, without waiting for them
+    fn receive(&mut self, fifo: &mut SioFifo) -> bool {
+        loop {
+            if self.len.is_some_and(|len| self.received >= len) {
+                return true;
+            }
+            let Some(word) = fifo.read() else { return false };
+            match self.len {
+                None => self.len = Some(word as usize),
+                Some(len) => {
+                    let count = (len - self.received).min(4);
+                    self.bytes[self.received..self.received + count].copy_from_slice(&word.to_le_bytes()[..count]);
+                    self.received += count;
+                }
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        self.len = None;
+        self.received = 0;
+    }
+}
+
+// what core 1 serves USB with, shared by its interrupt handlers and only ever touched on core 1
+struct Core1 {
+    usb: UsbSerial,
+    fifo: SioFifo,
+    output: TxBuffer<OUTPUT_LEN>,
+    incoming: Incoming,
+    // the complete packet in `incoming` found the queue full and waits for the host to read
+    waiting: bool,
 }
 
-pub fn poll_usb() {
+static CORE1: cortex_m::interrupt::Mutex<RefCell<Option<Core1>>> = cortex_m::interrupt::Mutex::new(RefCell::new(None));
+
+// core 1 from here on. USBCTRL_IRQ and SIO_IRQ_FIFO do the work and the core sleeps in between,
+// so it makes no bus or SIO traffic while neither the host nor core 0 has anything for it
+#[cfg_attr(host_test, allow(dead_code))]
+pub fn serve_usb(usb_bus: &'static UsbBusAllocator<UsbBus>, fifo: SioFifo) -> ! {
+    let core1 = Core1 {
+        usb: init_usb_serial(usb_bus),
+        fifo,
+        output: TxBuffer::new(),
+        incoming: Incoming { bytes: [0; OUTPUT_LEN], len: None, received: 0 },
+        waiting: false,
+    };
+    cortex_m::interrupt::free(|cs| CORE1.borrow(cs).replace(Some(core1)));
+
+    // in core 1's NVIC, core 0 leaves both masked
+    #[cfg(not(host_test))]
+    unsafe {
+        NVIC::unmask(Interrupt::USBCTRL_IRQ);
+        NVIC::unmask(Interrupt::SIO_IRQ_FIFO);
+    }
+    loop {
+        asm::wfi();
+    }
+}
+
+#[cfg(not(host_test))]
+#[interrupt]
+fn USBCTRL_IRQ() {
+    with_core1(Core1::service_usb);
+}
+
+// raised while core 1's end of the FIFO holds words
+#[cfg(not(host_test))]
+#[interrupt]
+fn SIO_IRQ_FIFO() {
+    with_core1(Core1::queue_packets);
+}
+
+#[cfg_attr(host_test, allow(dead_code))]
+fn with_core1(f: impl FnOnce(&mut Core1)) {
     cortex_m::interrupt::free(|cs| {
-        if let Some(ref mut usb) = *USB_SERIAL.borrow(cs).borrow_mut() {
-            usb.poll();
+        if let Some(ref mut core1) = *CORE1.borrow(cs).borrow_mut() {
+            f(core1);
         }
     });
 }
 
-// read available data from USB serial into buffer, returns byte count if any
-pub fn read_line(buf: &mut [u8]) -> Option<usize> {
-    use cortex_m::interrupt;
-    
-    // try to read more data from USB and add to the buffer
-    interrupt::free(|cs| {
-        if let Some(ref mut usb) = *USB_SERIAL.borrow(cs).borrow_mut() {
-            let mut tmp = [0u8; 64];
-            if let Ok(count) = usb.serial.read(&mut tmp) {
-                unsafe {
-                    let input_pos_ptr = core::ptr::addr_of_mut!(INPUT_POS);
-                    let input_buffer_ptr = core::ptr::addr_of_mut!(INPUT_BUFFER);
-
-                    for i in 0..count {
-                        if *input_pos_ptr < (*input_buffer_ptr).len() {
-                            (*input_buffer_ptr)[*input_pos_ptr] = tmp[i];
-                            *input_pos_ptr += 1;
-                        }
-                    }
+#[cfg_attr(host_test, allow(dead_code))]
+impl Core1 {
+    // USB events, what the host sent into INPUT, and as much of the queue as the host takes,
+    // which may make room for a waiting packet
+    fn service_usb(&mut self) {
+        self.usb.poll();
+
+        let mut tmp = [0u8; 64];
+        while let Ok(count @ 1..) = self.usb.serial.read(&mut tmp) {
+            critical_section::with(|cs| INPUT.borrow_ref_mut(cs).push(&tmp[..count]));
+        }
+
+        self.send();
+        if self.waiting {
+            self.queue_packets();
+        }
+    }
+
+    fn send(&mut self) {
+        while !self.output.is_empty() {
+            match self.usb.serial.write(self.output.pending()) {
+                Ok(count @ 1..) => {
+                    self.output.consume(count);
+                    DONE.store(DONE.load(Ordering::Relaxed).wrapping_add(count as u32), Ordering::Release);
                 }
+                _ => break,
             }
         }
-    });
-    
-    // Return whatever data is in the buffer
-    unsafe {
-        let input_pos_ptr = core::ptr::addr_of_mut!(INPUT_POS);
-        let input_buffer_ptr = core::ptr::addr_of_mut!(INPUT_BUFFER);
-
-        if *input_pos_ptr > 0 {
-            let data_len = (*input_pos_ptr).min(buf.len());
-            core::ptr::copy((*input_buffer_ptr).as_ptr(), buf.as_mut_ptr(), data_len);
-            *input_pos_ptr = 0;
-            return Some(data_len);
+    }
+
+    // core 0's packets into the queue until the FIFO is empty or one doesn't fit. That one waits
+    // with SIO_IRQ_FIFO masked, so core 0 stalls on a full FIFO, until USBCTRL_IRQ makes room.
+    // Nothing is ever dropped, a host that stops reading holds up the run
+    fn queue_packets(&mut self) {
+        while self.incoming.receive(&mut self.fifo) {
+            if !self.output.push(&self.incoming.bytes[..self.incoming.received]) {
+                if !self.waiting {
+                    WAITS.store(WAITS.load(Ordering::Relaxed) + 1, Ordering::Relaxed);
+                    self.start_waiting();
+                }
+                return;
+            }
+            self.incoming.clear();
+            if self.waiting {
+                self.stop_waiting();
+            }
+        }
+        self.send();
+    }
+
+    fn start_waiting(&mut self) {
+        #[cfg(not(host_test))]
+        NVIC::mask(Interrupt::SIO_IRQ_FIFO);
+        self.waiting = true;
+    }
+
+    // SIO_IRQ_FIFO goes off again right away if more packets came meanwhile
+    fn stop_waiting(&mut self) {
+        #[cfg(not(host_test))]
+        unsafe {
+            NVIC::unmask(Interrupt::SIO_IRQ_FIFO);
+        }
+        self.waiting = false;
+    }
+}
+
+// the next complete command line from USB serial, Err for one too long to be kept
+pub fn read_line(buf: &mut [u8; LINE_LEN]) -> Option<Result<usize, Overflow>> {
+    critical_section::with(|cs| INPUT.borrow_ref_mut(cs).read_line(buf))
+}
+
+// to core 1 as packets that fit its queue. Waits while the FIFO is full, for as long as the host
+// takes to make room
+fn write(bytes: &[u8]) {
+    cortex_m::interrupt::free(|cs| {
+        let mut fifo = FIFO.borrow(cs).borrow_mut();
+        let Some(fifo) = fifo.as_mut() else { return };
+        for packet in bytes.chunks(OUTPUT_LEN) {
+            fifo.write_blocking(packet.len() as u32);
+            for word in packet.chunks(4) {
+                let mut padded = [0u8; 4];
+                padded[..word.len()].copy_from_slice(word);
+                fifo.write_blocking(u32::from_le_bytes(padded));
+            }
+            QUEUED.store(QUEUED.load(Ordering::Relaxed).wrapping_add(packet.len() as u32), Ordering::Relaxed);
         }
+    });
+}
+
+// waits until core 1 passed on everything written so far, however long the host takes, so no
+// output is pending during the timed regions that follow. Called between them, never inside
+pub fn flush() {
+    while DONE.load(Ordering::Acquire) != QUEUED.load(Ordering::Relaxed) {
+        asm::delay(1_000);
     }
-    
-    None
 }
 
+// packets that had to wait for room since boot
+pub fn output_waits() -> u32 {
+    WAITS.load(Ordering::Relaxed)
+}
+
+pub fn set_binary(binary: bool) {
+    BINARY.store(binary, Ordering::Relaxed);
+}
+
+pub fn is_binary() -> bool {
+    BINARY.load(Ordering::Relaxed)
+}
+
+// a message as its text lines, or as one frame in binary output
+pub fn report(message: &Message) {
+    if !is_binary() {
+        crate::uprint!("{}", message);
+        return;
+    }
+    let mut buf = [0u8; MAX_FRAME];
+    match ee_protocol::encode(message, &mut buf) {
+        Ok(frame) => write(frame),
+        Err(_) => report(&Message::Error("Message too long for a frame")),
+    }
+}
+
+// a message holding one formatted line, on the stack and cut off past 240 bytes (a frame fits 256)
 #[macro_export]
-macro_rules! uprint {
-    ($($arg:tt)*) => {{
-        use core::fmt::Write as _;
-        cortex_m::interrupt::free(|cs| {
-            if let Some(ref mut usb) = *$crate::logger::USB_SERIAL.borrow(cs).borrow_mut() {
-                let _ = core::write!(usb, $($arg)*);
-                asm::delay(1_000);
+macro_rules! report_line {
+    ($variant:ident, $($arg:tt)*) => {{
+        let mut line = heapless::String::<240>::new();
+        let _ = core::fmt::Write::write_fmt(&mut line, format_args!($($arg)*));
+        $crate::logger::report(&ee_protocol::Message::$variant(&line));
+    }};
+}
 
-                usb.poll();
+#[macro_export]
+macro_rules! report_error {
+    ($($arg:tt)*) => { $crate::report_line!(Error, $($arg)*) };
+}
 
-                asm::delay(1_000);
-            }
-        });
+// "OK ..." and "ERR ..." replies to host commands
+#[macro_export]
+macro_rules! ack {
+    ($($arg:tt)*) => { $crate::report_line!(Ack, $($arg)*) };
+}
+
+#[macro_export]
+macro_rules! nak {
+    ($($arg:tt)*) => { $crate::report_line!(Nak, $($arg)*) };
+}
+
+// text to the host through core 1, see write
+#[macro_export]
+macro_rules! uprint {
+    ($($arg:tt)*) => {{
+        let _ = core::fmt::Write::write_fmt(&mut $crate::logger::Output, format_args!($($arg)*));
     }};
 }
\ No newline at end of file
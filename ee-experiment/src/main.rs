@@ -1,5 +1,6 @@
-#![no_std]
-#![no_main]
+// a std test binary on the host (host_test, from build.rs), the firmware otherwise
+#![cfg_attr(not(all(test, host_test)), no_std)]
+#![cfg_attr(not(all(test, host_test)), no_main)]
 
 // heap
 extern crate alloc;
@@ -7,7 +8,7 @@ use core::usize;
 
 use linked_list_allocator::LockedHeap;
 
-#[global_allocator]
+#[cfg_attr(not(all(test, host_test)), global_allocator)]
 static ALLOCATOR: LockedHeap = LockedHeap::empty();
 
 const HEAP_SIZE: usize = 16 * 1024; // kb
@@ -21,23 +22,119 @@ fn init_heap() {
 }
 
 
+#[cfg(not(all(test, host_test)))]
 use panic_halt as _;
 use rp235x_hal as hal;
-use cortex_m_rt;
-use cortex_m::{peripheral::Peripherals, asm};
+#[cfg(not(host_test))]
+use hal::multicore::{Multicore, Stack};
+use cortex_m::peripheral::Peripherals;
+use crypto_bigint::{U64, U128, U192, U256, U320, U384, U448, U512, U640, U704, U768, U832, U960, U1024, U1280, U1536, U2048};
 
+mod commands;
+mod line_buffer;
 mod logger;
 mod lookup;
+mod params;
 mod ecc;
 mod rsa;
+mod stats;
+mod timing;
+mod tx_buffer;
+
+use commands::{Command, Settings, MAX_ITERATIONS};
+use timing::{Clock, Elapsed, PhaseTimer, TimerClock};
+
+use core::ops::ControlFlow;
+
+use ee_protocol::{Message, RawSample, RunHeader, SizeHeader, TrialResult};
 
 // consts
+#[cfg(not(host_test))]
 static mut USB_BUS: Option<usb_device::bus::UsbBusAllocator<hal::usb::UsbBus>> = None;
+// core 1 serves USB, 8 KB
+#[cfg(not(host_test))]
+static CORE1_STACK: Stack<2048> = Stack::new();
 const XTAL_FREQ_HZ: u32 = 12_000_000;
+// iterations per trial until SET iterations changes it
 const TRIALS_PER_KEY: usize = 15;
+// whether checking the shared secret / decrypted session key counts towards the measured time
+const VERIFY_IN_WINDOW: bool = !cfg!(feature = "untimed-verify");
+// keygen-timing derives d from p, q and e on the device instead of reading it from the table, and
+// leaves both key generations out of the exchange totals (they are reported on their own)
+const KEYGEN_TIMING: bool = cfg!(feature = "keygen-timing");
+// point representation inside ECC scalar multiplication
+const ECC_COORDINATES: ecc::Coordinates =
+    if cfg!(feature = "jacobian") { ecc::Coordinates::Jacobian } else { ecc::Coordinates::Affine };
+// constant-time ladder or the variable-time double-and-add, unless START picks another method
+const ECC_SCALAR_MULT: ecc::ScalarMult =
+    if cfg!(feature = "ladder") { ecc::ScalarMult::Ladder } else { ecc::ScalarMult::DoubleAndAdd };
+
+// what the host asked for on the START line, the build's features are the defaults. Lasts for
+// one run, unlike the Settings from SET and SELECT
+struct RunConfig {
+    scalar_mult: ecc::ScalarMult,
+    // k * G through primegen's generator comb
+    comb: bool,
+    decryption: rsa::Decryption,
+    exponentiation: rsa::Exponentiation,
+    // every iteration's cycles on a line of its own
+    raw: bool,
+    // min, max, median and standard deviation of each trial's iterations
+    summary: bool,
+    // checksummed frames for ee-decoder instead of text
+    binary: bool,
+}
+
+// the options after START: [double-and-add | ladder | wnaf:<2-6>] [comb] [plain | crt | crt-checked]
+// [square-and-multiply | k-ary:<1-6> | sliding:<1-6> | fixed:<1-6> | builtin] [raw] [summary]
+// why START was refused
+enum StartError<'a> {
+    UnknownOption(&'a str),
+    // the comb is variable-time, k * G would silently stop being a ladder
+    LadderWithComb,
+}
+
+// [binary]
+fn parse_start(options: &str) -> Result<RunConfig, StartError<'_>> {
+    let mut config = RunConfig {
+        scalar_mult: ECC_SCALAR_MULT,
+        comb: false,
+        decryption: rsa::Decryption::Plain,
+        exponentiation: rsa::Exponentiation::SquareAndMultiply,
+        raw: false,
+        summary: false,
+        binary: false,
+    };
+    for word in options.split_ascii_whitespace() {
+        if word == "comb" {
+            config.comb = true;
+        } else if word == "raw" {
+            config.raw = true;
+        } else if word == "summary" {
+            config.summary = true;
+        } else if word == "binary" {
+            config.binary = true;
+        } else if let Ok(scalar_mult) = word.parse() {
+            config.scalar_mult = scalar_mult;
+        } else if let Ok(decryption) = word.parse() {
+            config.decryption = decryption;
+        } else if let Ok(exponentiation) = word.parse() {
+            config.exponentiation = exponentiation;
+        } else {
+            return Err(StartError::UnknownOption(word));
+        }
+    }
+    if config.comb && config.scalar_mult == ecc::ScalarMult::Ladder {
+        return Err(StartError::LadderWithComb);
+    }
+    Ok(config)
+}
 
 pub fn exit() -> ! {
-    uprint!("Exiting...\n");
+    // nothing but frames once the host expects them
+    if !logger::is_binary() {
+        uprint!("Exiting...\n");
+    }
     loop {}
 }
 
@@ -45,7 +142,7 @@ pub fn exit() -> ! {
 #[used]
 pub static IMAGE_DEF: hal::block::ImageDef = hal::block::ImageDef::secure_exe();
 
-#[cortex_m_rt::entry]
+#[cfg_attr(not(all(test, host_test)), cortex_m_rt::entry)]
 fn main() -> ! {
     // setup
     init_heap();
@@ -71,7 +168,11 @@ fn main() -> ! {
     )
     .unwrap();
 
-    // init USB serial
+    // 64-bit cycles and microseconds, the timer ticks at 1 MHz once the clocks are up
+    let timer = hal::Timer::new_timer0(pac.TIMER0, &mut pac.RESETS, &clocks);
+    let clock = TimerClock::new(&cp.DWT, timer, hal::Clock::freq(&clocks.system_clock).to_Hz());
+
+    // init USB serial, served by core 1 so that none of its interrupts reach the measurements here
     let usb_bus = hal::usb::UsbBus::new(
         pac.USB,
         pac.USB_DPRAM,
@@ -79,75 +180,419 @@ fn main() -> ! {
         true,
         &mut pac.RESETS,
     );
-    unsafe {
-        USB_BUS = Some(usb_device::bus::UsbBusAllocator::new(usb_bus));
-        let usb_bus_ref = core::ptr::addr_of!(USB_BUS)
-            .as_ref()
-            .unwrap()
-            .as_ref()
-            .unwrap();
-        logger::init_usb_serial(usb_bus_ref);
+    let mut sio = hal::Sio::new(pac.SIO);
+    start_core1(&mut pac.PSM, &mut pac.PPB, &mut sio.fifo, usb_bus);
+    logger::init(sio.fifo);
+
+    // checked before the host connects, reported once it does
+    let table = params::Table::load();
+    let mut settings = Settings::default();
+
+    // commands until START, then the run, then commands again
+    loop {
+        let config = wait_for_start(&table, &mut settings);
+        // START only gets through with a valid table
+        let Ok(table) = &table else { exit() };
+
+        logger::report(&Message::RunHeader(RunHeader {
+            coordinates: &label(&config.scalar_mult.coordinates(ECC_COORDINATES)),
+            scalar_mult: &label(&config.scalar_mult),
+            comb_teeth: if config.comb { table.comb_teeth() as u16 } else { 0 },
+            decryption: &label(&config.decryption),
+            exponentiation: &label(&config.exponentiation),
+            raw: config.raw,
+            summary: config.summary,
+            iterations: settings.iterations as u16,
+        }));
+
+        for size_idx in (0..table.rows()).filter(|&row| settings.rows.contains(row)) {
+            let (ecc_bits, rsa_bits) = table.row(size_idx);
+            logger::report(&Message::SizeHeader(SizeHeader { index: size_idx as u16, ecc_bits, rsa_bits }));
+            if dispatch_row(table, size_idx, &config, &settings, &clock).is_break() {
+                report_error!("Run aborted by the host");
+                break;
+            }
+        }
+
+        logger::report(&Message::Complete);
     }
+}
 
-    // wait for "START" over serial
-    let mut buf = [0u8; 16];
+// answers commands until a START the run can go ahead with
+fn wait_for_start(table: &Result<params::Table, params::TableError>, settings: &mut Settings) -> RunConfig {
+    let mut buf = [0u8; logger::LINE_LEN];
     loop {
-        logger::poll_usb();
-        if let Some(len) = logger::read_line(&mut buf) {
-            if let Ok(s) = core::str::from_utf8(&buf[..len]) {
-                if s == "START" {
-                    break;
+        let Some(line) = next_line(&mut buf) else { continue };
+        let command = match commands::parse(line) {
+            Ok(command) => command,
+            Err(reason) => {
+                nak!("{}: {}", line, reason);
+                continue;
+            }
+        };
+        match command {
+            Command::Ping => ack!("{}", line),
+            Command::Info => info(line, table, settings),
+            Command::SetIterations(iterations) => {
+                settings.iterations = iterations;
+                ack!("{}", line);
+            }
+            Command::SetOutput { binary } => {
+                // the reply is already in the new format
+                logger::set_binary(binary);
+                ack!("{}", line);
+            }
+            Command::SelectRows(rows) => match (table, rows.last()) {
+                (Ok(table), Some(last)) if last >= table.rows() => {
+                    nak!("{}: the table has rows 0 to {}", line, table.rows() - 1)
+                }
+                _ => {
+                    settings.rows = rows;
+                    ack!("{}", line);
+                }
+            },
+            Command::SelectAlgorithms { ecc, rsa } => {
+                settings.ecc = ecc;
+                settings.rsa = rsa;
+                ack!("{}", line);
+            }
+            Command::Start(options) => {
+                let config = match parse_start(options) {
+                    Ok(config) => config,
+                    Err(StartError::UnknownOption(option)) => {
+                        nak!("{}: unknown option {}", line, option);
+                        continue;
+                    }
+                    Err(StartError::LadderWithComb) => {
+                        nak!("{}: the comb is not constant-time, use it without ladder", line);
+                        continue;
+                    }
+                };
+                let table = match table {
+                    Ok(table) => table,
+                    Err(e) => {
+                        report_error!("Invalid parameter table: {}", e);
+                        exit()
+                    }
+                };
+                if config.comb && table.comb_teeth() == 0 {
+                    nak!("{}: no generator comb in the parameter table, regenerate it with --comb-teeth", line);
+                    continue;
+                }
+                if config.binary {
+                    logger::set_binary(true);
                 }
+                ack!("{}", line);
+                return config;
             }
+            Command::Abort => nak!("{}: no run in progress", line),
+            Command::Status => {
+                ack!("{} idle, output waited {} times", line, logger::output_waits());
+            }
+        }
+    }
+}
+
+// between the iterations of a run, outside the timed windows: only what doesn't change the run
+fn poll_commands(size_idx: usize, trial: usize, iteration: usize) -> ControlFlow<()> {
+    let mut buf = [0u8; logger::LINE_LEN];
+    while let Some(line) = next_line(&mut buf) {
+        match commands::parse(line) {
+            Ok(Command::Ping) => ack!("{}", line),
+            Ok(Command::Status) => {
+                ack!(
+                    "{} running row {} trial {} iteration {}, output waited {} times",
+                    line, size_idx, trial, iteration, logger::output_waits()
+                );
+            }
+            Ok(Command::Abort) => {
+                ack!("{}", line);
+                return ControlFlow::Break(());
+            }
+            Ok(_) => nak!("{}: a run is in progress, ABORT it first", line),
+            Err(reason) => nak!("{}: {}", line, reason),
         }
     }
-    
-    uprint!("=== Started EE Experiment ===\n");
-
-    for size_idx in 0..lookup::ECC_V_RSA.len() {
-        let (ecc_bits, rsa_bits) = lookup::ECC_V_RSA[size_idx];
-        uprint!("=== ECC {} / RSA {} bits ===\n", ecc_bits, rsa_bits);
-
-        for i in 0..(lookup::TRIALS as usize) {
-            let mut ecc_time: u64 = 0;
-            let mut rsa_time: u64 = 0;
-            let mut ecc_fails: u16 = 0;
-            let mut rsa_fails: u16 = 0;
-            
-            let trial_data: lookup::KeySize = lookup::LOOKUP_TABLE[size_idx * (lookup::TRIALS as usize) + i];
-
-            let ecc_ctx = ecc::EccCtx::new(trial_data.ecc.curve.p, trial_data.ecc.curve.a);
-            let rsa_ctx = rsa::RsaCtx::new(&trial_data.rsa);
-
-            for _j in 0..TRIALS_PER_KEY {
-                // ECC
-                let mut start = cp.DWT.cyccnt.read();
-                if !ecc::ecdh(trial_data.ecc, &ecc_ctx) {
+    ControlFlow::Continue(())
+}
+
+// the next command line that arrived, lines that can't be commands are answered here
+fn next_line(buf: &mut [u8; logger::LINE_LEN]) -> Option<&str> {
+    match logger::read_line(buf)? {
+        Ok(len) => match core::str::from_utf8(&buf[..len]) {
+            Ok(line) => Some(line.trim()).filter(|line| !line.is_empty()),
+            Err(_) => {
+                nak!("<{} bytes>: not UTF-8", len);
+                None
+            }
+        },
+        // either a line that never fits or one that came while unread lines filled the ring
+        Err(overflow) => {
+            nak!("<{} bytes>: dropped, line too long or input buffer full", overflow.dropped);
+            None
+        }
+    }
+}
+
+fn info(line: &str, table: &Result<params::Table, params::TableError>, settings: &Settings) {
+    let table = match table {
+        Ok(table) => table,
+        Err(e) => {
+            nak!("{}: invalid parameter table: {}", line, e);
+            return;
+        }
+    };
+    ack!(
+        "{} version {} rows {} trials {} comb-teeth {} iterations {} selected-rows {} algorithms {} coordinates {:?} keygen-timing {} verify-in-window {} output {}",
+        line,
+        env!("CARGO_PKG_VERSION"),
+        table.rows(),
+        table.trials(),
+        table.comb_teeth(),
+        settings.iterations,
+        settings.rows,
+        match (settings.ecc, settings.rsa) {
+            (true, false) => "ecc",
+            (false, true) => "rsa",
+            _ => "all",
+        },
+        ECC_SCALAR_MULT.coordinates(ECC_COORDINATES),
+        KEYGEN_TIMING,
+        VERIFY_IN_WINDOW,
+        if logger::is_binary() { "binary" } else { "text" },
+    );
+}
+
+#[cfg(not(host_test))]
+fn start_core1(psm: &mut hal::pac::PSM, ppb: &mut hal::pac::PPB, fifo: &mut hal::sio::SioFifo, usb_bus: hal::usb::UsbBus) {
+    let mut multicore = Multicore::new(psm, ppb, fifo);
+    let spawned = multicore.cores()[1].spawn(CORE1_STACK.take().unwrap(), move || {
+        // core 1's end of the FIFO
+        let fifo = hal::Sio::new(unsafe { hal::pac::Peripherals::steal() }.SIO).fifo;
+        unsafe {
+            USB_BUS = Some(usb_device::bus::UsbBusAllocator::new(usb_bus));
+            let usb_bus_ref = core::ptr::addr_of!(USB_BUS)
+                .as_ref()
+                .unwrap()
+                .as_ref()
+                .unwrap();
+            logger::serve_usb(usb_bus_ref, fifo)
+        }
+    });
+    // without core 1 there is no way to tell the host
+    if spawned.is_err() {
+        exit()
+    }
+}
+
+// host builds have no second core to serve USB on
+#[cfg(host_test)]
+fn start_core1(_: &mut hal::pac::PSM, _: &mut hal::pac::PPB, _: &mut hal::sio::SioFifo, _: hal::usb::UsbBus) {}
+
+// a setting's name for the run header, as {:?} prints it
+fn label(value: &impl core::fmt::Debug) -> heapless::String<24> {
+    let mut label = heapless::String::new();
+    let _ = core::fmt::Write::write_fmt(&mut label, format_args!("{:?}", value));
+    label
+}
+
+// smallest crypto-bigint widths (RSA modulus, RSA prime, ECC field) that fit each ECC_V_RSA row,
+// primegen's row_widths() emits the table at the same ones
+fn dispatch_row(table: &params::Table, size_idx: usize, config: &RunConfig, settings: &Settings, clock: &TimerClock) -> ControlFlow<()> {
+    match table.row(size_idx) {
+        (16, 32) => run_row::<{ U64::LIMBS }, { U64::LIMBS }, { U64::LIMBS }>(table, size_idx, config, settings, clock),
+        (32, 64) => run_row::<{ U64::LIMBS }, { U64::LIMBS }, { U64::LIMBS }>(table, size_idx, config, settings, clock),
+        (48, 112) => run_row::<{ U128::LIMBS }, { U64::LIMBS }, { U64::LIMBS }>(table, size_idx, config, settings, clock),
+        (64, 176) => run_row::<{ U192::LIMBS }, { U128::LIMBS }, { U64::LIMBS }>(table, size_idx, config, settings, clock),
+        (80, 264) => run_row::<{ U320::LIMBS }, { U192::LIMBS }, { U128::LIMBS }>(table, size_idx, config, settings, clock),
+        (96, 368) => run_row::<{ U384::LIMBS }, { U192::LIMBS }, { U128::LIMBS }>(table, size_idx, config, settings, clock),
+        (112, 496) => run_row::<{ U512::LIMBS }, { U256::LIMBS }, { U128::LIMBS }>(table, size_idx, config, settings, clock),
+        (128, 648) => run_row::<{ U704::LIMBS }, { U384::LIMBS }, { U128::LIMBS }>(table, size_idx, config, settings, clock),
+        (144, 824) => run_row::<{ U832::LIMBS }, { U448::LIMBS }, { U192::LIMBS }>(table, size_idx, config, settings, clock),
+        (160, 1024) => run_row::<{ U1024::LIMBS }, { U512::LIMBS }, { U192::LIMBS }>(table, size_idx, config, settings, clock),
+        (176, 1256) => run_row::<{ U1280::LIMBS }, { U640::LIMBS }, { U192::LIMBS }>(table, size_idx, config, settings, clock),
+        (192, 1520) => run_row::<{ U1536::LIMBS }, { U768::LIMBS }, { U192::LIMBS }>(table, size_idx, config, settings, clock),
+        (208, 1806) => run_row::<{ U2048::LIMBS }, { U960::LIMBS }, { U256::LIMBS }>(table, size_idx, config, settings, clock),
+        (ecc_bits, rsa_bits) => {
+            report_error!("No integer widths for ECC {} / RSA {} bits", ecc_bits, rsa_bits);
+            exit()
+        }
+    }
+}
+
+// every trial of one row, N/P/E limbs as picked by dispatch_row
+// ControlFlow::Break once the host aborted the run
+fn run_row<const N: usize, const P: usize, const E: usize>(
+    table: &params::Table,
+    size_idx: usize,
+    config: &RunConfig,
+    settings: &Settings,
+    clock: &impl Clock,
+) -> ControlFlow<()> {
+    let iterations = settings.iterations;
+    for i in 0..table.trials() {
+        let mut ecc_phases = PhaseTimer::<_, { ecc::ECC_PHASES }>::new(clock);
+        let mut rsa_phases = PhaseTimer::<_, { rsa::RSA_PHASES }>::new(clock);
+        // checking the results, counted towards the totals with VERIFY_IN_WINDOW
+        let (mut ecc_verify_time, mut rsa_verify_time) = (Elapsed::default(), Elapsed::default());
+        let mut ecc_fails: u16 = 0;
+        let mut rsa_fails: u16 = 0;
+        // raised during the measurements, see timing::masked
+        let (mut ecc_interrupts, mut rsa_interrupts) = (0u32, 0u32);
+        
+        let trial_data: lookup::KeySize<N, P, E> = match table.entry(size_idx, i) {
+            Ok(entry) => entry,
+            Err(e) => {
+                report_error!("Invalid parameter table: {}", e);
+                exit()
+            }
+        };
+
+        // the comb is rebuilt per trial, its generator changes with the curve
+        let comb = if config.comb {
+            match ecc::Comb::new(&trial_data.ecc.curve, table.comb_teeth(), |idx| table.comb_point(size_idx, i, idx)) {
+                Some(comb) => Some(comb),
+                None => {
+                    report_error!("Invalid generator comb for ECC {} bits", trial_data.ecc_bits);
+                    exit()
+                }
+            }
+        } else {
+            None
+        };
+
+        // each iteration's exchange window, for raw and summary
+        let mut ecc_samples = [0u64; MAX_ITERATIONS];
+        let mut rsa_samples = [0u64; MAX_ITERATIONS];
+        let (ecc_samples, rsa_samples) = (&mut ecc_samples[..iterations], &mut rsa_samples[..iterations]);
+
+        for j in 0..iterations {
+            // everything reported so far goes out before timing starts, the USB interrupt stays quiet
+            logger::flush();
+
+            // ECC, unless SELECT algorithms left it out
+            if settings.ecc {
+                let before = ecc_window(&ecc_phases, ecc_verify_time).cycles;
+                // interrupts wait until the exchange and its check are over
+                let (ecc_ok, interrupts) = timing::masked(|| {
+                    ecc_phases.restart();
+                    let secrets =
+                        ecc::ecdh(&trial_data.ecc, ECC_COORDINATES, config.scalar_mult, comb.as_ref(), &mut ecc_phases);
+                    let start = clock.now();
+                    let ecc_ok = secrets.agree();
+                    ecc_verify_time += clock.now().since(&start);
+                    ecc_ok
+                });
+                ecc_interrupts += interrupts;
+                if !ecc_ok {
                     ecc_fails += 1;
-                    uprint!("[ERROR] ECC key exchange failed");
+                    report_error!("ECC key exchange failed");
                 }
-                let mut end = cp.DWT.cyccnt.read();
-                ecc_time += end.wrapping_sub(start) as u64;
-                logger::poll_usb();
+                ecc_samples[j] = ecc_window(&ecc_phases, ecc_verify_time).cycles - before;
+            }
+            logger::flush();
 
-                // RSA
-                start = cp.DWT.cyccnt.read();
-                if !rsa::key_transport(trial_data.rsa, &rsa_ctx) {
+            // RSA
+            if settings.rsa {
+                let before = rsa_window(&rsa_phases, rsa_verify_time).cycles;
+                let (rsa_ok, interrupts) = timing::masked(|| {
+                    rsa_phases.restart();
+                    let decrypted = rsa::key_transport(
+                        &trial_data.rsa,
+                        config.decryption,
+                        config.exponentiation,
+                        KEYGEN_TIMING,
+                        &mut rsa_phases,
+                    );
+                    let start = clock.now();
+                    let rsa_ok = rsa::recovered(decrypted, &trial_data.rsa);
+                    rsa_verify_time += clock.now().since(&start);
+                    rsa_ok
+                });
+                rsa_interrupts += interrupts;
+                if !rsa_ok {
                     rsa_fails += 1;
-                    uprint!("[ERROR] RSA key transport failed");
+                    report_error!("RSA key transport failed");
                 }
-                end = cp.DWT.cyccnt.read();
-                rsa_time += end.wrapping_sub(start) as u64;
-                logger::poll_usb();
+                rsa_samples[j] = rsa_window(&rsa_phases, rsa_verify_time).cycles - before;
             }
 
-            let avg_ecc = (ecc_time / (TRIALS_PER_KEY as u64)) as u32;
-            let avg_rsa = (rsa_time / (TRIALS_PER_KEY as u64)) as u32;
-            uprint!("Trial #{}: ECC = {}, RSA = {}, ECC fails = {}, RSA fails = {}\n", i + 1, avg_ecc, avg_rsa, ecc_fails, rsa_fails);
+            // STATUS, PING and ABORT, answered outside the timed windows
+            if poll_commands(size_idx, i + 1, j + 1).is_break() {
+                return ControlFlow::Break(());
+            }
+        }
+
+        let avg = |cycles: u64| cycles / (iterations as u64);
+        let ecc_phase = |phase: ecc::EccPhase| avg(ecc_phases.total(phase as usize).cycles);
+        let rsa_phase = |phase: rsa::RsaPhase| avg(rsa_phases.total(phase as usize).cycles);
+
+        let ecc_time = ecc_window(&ecc_phases, ecc_verify_time);
+        let rsa_time = rsa_window(&rsa_phases, rsa_verify_time);
+        let overflowed = (0..ecc::ECC_PHASES).any(|phase| ecc_phases.total(phase).overflowed)
+            || (0..rsa::RSA_PHASES).any(|phase| rsa_phases.total(phase).overflowed)
+            || ecc_verify_time.overflowed
+            || rsa_verify_time.overflowed;
+
+        // reported once the trial is over so the USB traffic stays out of the timed loop
+        if config.raw {
+            for (j, (&ecc_cycles, &rsa_cycles)) in ecc_samples.iter().zip(rsa_samples.iter()).enumerate() {
+                logger::report(&Message::RawSample(RawSample {
+                    size: size_idx as u16,
+                    trial: i as u16 + 1,
+                    iteration: j as u16 + 1,
+                    ecc_cycles,
+                    rsa_cycles,
+                }));
+            }
         }
-        uprint!("\n");
+
+        logger::report(&Message::TrialResult(TrialResult {
+            trial: i as u16 + 1,
+            ecc_cycles: avg(ecc_time.cycles),
+            rsa_cycles: avg(rsa_time.cycles),
+            ecc_fails,
+            rsa_fails,
+            ecc_micros: avg(ecc_time.micros),
+            rsa_micros: avg(rsa_time.micros),
+            keygen: KEYGEN_TIMING.then(|| [ecc_phase(ecc::EccPhase::Keygen), rsa_phase(rsa::RsaPhase::Exponent)]),
+            ecc_phases: [
+                ecc_phase(ecc::EccPhase::Setup),
+                ecc_phase(ecc::EccPhase::Keygen),
+                ecc_phase(ecc::EccPhase::SharedSecret),
+            ],
+            rsa_phases: [
+                rsa_phase(rsa::RsaPhase::Exponent),
+                rsa_phase(rsa::RsaPhase::Encryption),
+                rsa_phase(rsa::RsaPhase::Decryption),
+            ],
+            summary: config
+                .summary
+                .then(|| [stats::summarize(ecc_samples), stats::summarize(rsa_samples)]),
+            interrupts: [ecc_interrupts, rsa_interrupts],
+            overflowed,
+        }));
+    }
+    ControlFlow::Continue(())
+}
+
+// the exchange windows out of a trial's phases so far: no context setup, no key generation with
+// keygen-timing, and the check of the result with VERIFY_IN_WINDOW
+fn ecc_window<C: Clock>(phases: &PhaseTimer<C, { ecc::ECC_PHASES }>, verify: Elapsed) -> Elapsed {
+    let mut window = phases.total(ecc::EccPhase::SharedSecret as usize);
+    if !KEYGEN_TIMING {
+        window += phases.total(ecc::EccPhase::Keygen as usize);
+    }
+    if VERIFY_IN_WINDOW {
+        window += verify;
     }
+    window
+}
 
-    uprint!("=== Experiment Complete ===\n");
-    exit()
+fn rsa_window<C: Clock>(phases: &PhaseTimer<C, { rsa::RSA_PHASES }>, verify: Elapsed) -> Elapsed {
+    let mut window = phases.total(rsa::RsaPhase::Encryption as usize) + phases.total(rsa::RsaPhase::Decryption as usize);
+    if VERIFY_IN_WINDOW {
+        window += verify;
+    }
+    window
 }
@@ -0,0 +1,353 @@
+use core::fmt;
+
+use crate::lookup::{KeySize, Point};
+#[cfg(not(feature = "param-blob"))]
+use crate::lookup::{ECC, EccCurve, RSA};
+
+// where the per-trial parameters come from, the generated LOOKUP_TABLE by default or the
+// binary blob written next to it by primegen with the `param-blob` feature
+pub struct Table {
+    #[cfg(feature = "param-blob")]
+    blob: blob::Blob,
+}
+
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(not(feature = "param-blob"), allow(dead_code))]
+pub enum TableError {
+    TooShort,
+    BadMagic,
+    UnsupportedVersion(u16),
+    BadEntryLen(u16),
+    BadLength { expected: usize, actual: usize },
+    Checksum { expected: u32, actual: u32 },
+    // the entry does not fit the integer widths main picked for its row
+    WidthMismatch { ecc_bits: u16, rsa_bits: u16 },
+    CombTeeth(usize),
+}
+
+impl fmt::Display for TableError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TableError::TooShort => write!(f, "blob shorter than its header"),
+            TableError::BadMagic => write!(f, "bad magic, not a primegen blob"),
+            TableError::UnsupportedVersion(v) => write!(f, "unsupported blob version {}", v),
+            TableError::BadEntryLen(len) => write!(f, "entry length {} does not match KeySize", len),
+            TableError::BadLength { expected, actual } => {
+                write!(f, "blob is {} bytes, header says {}", actual, expected)
+            }
+            TableError::Checksum { expected, actual } => {
+                write!(f, "checksum mismatch, expected {:08x} got {:08x}", expected, actual)
+            }
+            TableError::WidthMismatch { ecc_bits, rsa_bits } => {
+                write!(f, "ECC {} / RSA {} entry does not fit its row's integer widths", ecc_bits, rsa_bits)
+            }
+            TableError::CombTeeth(teeth) => write!(f, "generator comb has {} teeth, at most {} fit", teeth, crate::ecc::MAX_COMB_TEETH),
+        }
+    }
+}
+
+#[cfg(not(feature = "param-blob"))]
+impl Table {
+    pub fn load() -> Result<Self, TableError> {
+        Ok(Table {})
+    }
+
+    pub fn rows(&self) -> usize {
+        crate::lookup::ECC_V_RSA.len()
+    }
+
+    pub fn row(&self, size_idx: usize) -> (u16, u16) {
+        crate::lookup::ECC_V_RSA[size_idx]
+    }
+
+    pub fn trials(&self) -> usize {
+        crate::lookup::TRIALS as usize
+    }
+
+    pub fn entry<const N: usize, const P: usize, const E: usize>(
+        &self,
+        size_idx: usize,
+        trial: usize,
+    ) -> Result<KeySize<N, P, E>, TableError> {
+        let (ecc_bits, rsa_bits) = self.row(size_idx);
+        crate::lookup::entry(size_idx, trial).ok_or(TableError::WidthMismatch { ecc_bits, rsa_bits })
+    }
+
+    // 0 when primegen ran without --comb-teeth
+    pub fn comb_teeth(&self) -> usize {
+        crate::lookup::COMB_TEETH as usize
+    }
+
+    pub fn comb_point<const E: usize>(&self, size_idx: usize, trial: usize, idx: usize) -> Option<Point<E>> {
+        crate::lookup::comb_point(size_idx, trial, idx)
+    }
+}
+
+// the generated ROW_ arrays are typed at primegen's widths, main asks for its own, every
+// (row, widths) pair is instantiated but only the matching one returns Some
+#[cfg(not(feature = "param-blob"))]
+impl<const N: usize, const P: usize, const E: usize> KeySize<N, P, E> {
+    pub fn cast<const N2: usize, const P2: usize, const E2: usize>(&self) -> Option<KeySize<N2, P2, E2>> {
+        if N != N2 || P != P2 || E != E2 {
+            return None;
+        }
+        let rsa = &self.rsa;
+        let curve = &self.ecc.curve;
+        Some(KeySize {
+            ecc_bits: self.ecc_bits,
+            rsa_bits: self.rsa_bits,
+            rsa: RSA {
+                session_key: rsa.session_key.resize(),
+                exponent: rsa.exponent.resize(),
+                p: rsa.p.resize(),
+                q: rsa.q.resize(),
+                d: rsa.d.resize(),
+                dp: rsa.dp.resize(),
+                dq: rsa.dq.resize(),
+                qinv: rsa.qinv.resize(),
+            },
+            ecc: ECC {
+                curve: EccCurve {
+                    a: curve.a.resize(),
+                    b: curve.b.resize(),
+                    p: curve.p.resize(),
+                    order: curve.order.resize(),
+                    cofactor: curve.cofactor,
+                    generator: curve.generator.cast()?,
+                },
+                private_key1: self.ecc.private_key1.resize(),
+                private_key2: self.ecc.private_key2.resize(),
+            },
+        })
+    }
+}
+
+#[cfg(not(feature = "param-blob"))]
+impl<const E: usize> Point<E> {
+    pub fn cast<const E2: usize>(&self) -> Option<Point<E2>> {
+        if E != E2 {
+            return None;
+        }
+        Some(Point { x: self.x.resize(), y: self.y.resize() })
+    }
+}
+
+#[cfg(feature = "param-blob")]
+impl Table {
+    // validates the header and checksum once, entries are decoded straight from flash on access
+    pub fn load() -> Result<Self, TableError> {
+        Ok(Table { blob: blob::Blob::parse(blob::BLOB)? })
+    }
+
+    pub fn rows(&self) -> usize {
+        self.blob.row_count
+    }
+
+    pub fn row(&self, size_idx: usize) -> (u16, u16) {
+        self.blob.row(size_idx)
+    }
+
+    pub fn trials(&self) -> usize {
+        self.blob.trials
+    }
+
+    pub fn entry<const N: usize, const P: usize, const E: usize>(
+        &self,
+        size_idx: usize,
+        trial: usize,
+    ) -> Result<KeySize<N, P, E>, TableError> {
+        let (ecc_bits, rsa_bits) = self.row(size_idx);
+        self.blob
+            .entry(size_idx * self.trials() + trial)
+            .ok_or(TableError::WidthMismatch { ecc_bits, rsa_bits })
+    }
+
+    pub fn comb_teeth(&self) -> usize {
+        self.blob.comb_teeth
+    }
+
+    pub fn comb_point<const E: usize>(&self, size_idx: usize, trial: usize, idx: usize) -> Option<Point<E>> {
+        self.blob.comb_point(size_idx * self.trials() + trial, idx)
+    }
+}
+
+// reader for the format written by primegen/src/blob.rs
+#[cfg(feature = "param-blob")]
+mod blob {
+    use crypto_bigint::{Limb, Uint, Word};
+
+    use super::TableError;
+    use crate::lookup::{ECC, EccCurve, KeySize, Point, RSA};
+
+    pub static BLOB: &[u8] = include_bytes!("lookup.bin");
+
+    const MAGIC: [u8; 4] = *b"EEPT";
+    const VERSION: u16 = 3;
+    const HEADER_LEN: usize = 20;
+    const ROW_LEN: usize = 4;
+    const ENTRY_LEN: usize = 2 + 2 + 2 * 256 + 32 + 5 * 128 + 8 * 32 + 4;
+    const COMB_POINT_LEN: usize = 2 * 32;
+
+    pub struct Blob {
+        bytes: &'static [u8],
+        pub row_count: usize,
+        pub trials: usize,
+        pub comb_teeth: usize,
+    }
+
+    fn u16_at(bytes: &[u8], offset: usize) -> u16 {
+        u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+    }
+
+    fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]])
+    }
+
+    // CRC-32 (IEEE 802.3, reflected), bitwise to avoid a 1 KB table in flash
+    fn crc32(data: &[u8]) -> u32 {
+        let mut crc = !0u32;
+        for &byte in data {
+            crc ^= byte as u32;
+            for _ in 0..8 {
+                let mask = (crc & 1).wrapping_neg();
+                crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+            }
+        }
+        !crc
+    }
+
+    // sequential little-endian field reader over one entry
+    struct Cursor<'a> {
+        bytes: &'a [u8],
+        pos: usize,
+    }
+
+    impl<'a> Cursor<'a> {
+        fn take(&mut self, len: usize) -> &'a [u8] {
+            let out = &self.bytes[self.pos..self.pos + len];
+            self.pos += len;
+            out
+        }
+
+        fn u16(&mut self) -> u16 {
+            u16_at(self.take(2), 0)
+        }
+
+        fn u32(&mut self) -> u32 {
+            u32_at(self.take(4), 0)
+        }
+
+        // a fixed-width field narrowed to L limbs, None if it has bits above them
+        fn uint<const L: usize>(&mut self, len: usize) -> Option<Uint<L>> {
+            let mut limbs = [Limb::ZERO; L];
+            for (i, chunk) in self.take(len).chunks(Limb::BYTES).enumerate() {
+                let mut word = [0u8; Limb::BYTES];
+                word.copy_from_slice(chunk);
+                let word = Word::from_le_bytes(word);
+                match limbs.get_mut(i) {
+                    Some(limb) => *limb = Limb(word),
+                    None if word != 0 => return None,
+                    None => {}
+                }
+            }
+            Some(Uint::new(limbs))
+        }
+
+        fn u256<const L: usize>(&mut self) -> Option<Uint<L>> {
+            self.uint(32)
+        }
+
+        fn u1024<const L: usize>(&mut self) -> Option<Uint<L>> {
+            self.uint(128)
+        }
+
+        fn u2048<const L: usize>(&mut self) -> Option<Uint<L>> {
+            self.uint(256)
+        }
+    }
+
+    impl Blob {
+        pub fn parse(bytes: &'static [u8]) -> Result<Self, TableError> {
+            if bytes.len() < HEADER_LEN {
+                return Err(TableError::TooShort);
+            }
+            if bytes[..4] != MAGIC {
+                return Err(TableError::BadMagic);
+            }
+            let version = u16_at(bytes, 4);
+            if version != VERSION {
+                return Err(TableError::UnsupportedVersion(version));
+            }
+            let row_count = u16_at(bytes, 6) as usize;
+            let trials = u16_at(bytes, 8) as usize;
+            let entry_len = u16_at(bytes, 10);
+            if entry_len as usize != ENTRY_LEN {
+                return Err(TableError::BadEntryLen(entry_len));
+            }
+
+            let comb_teeth = u16_at(bytes, 16) as usize;
+            if comb_teeth > crate::ecc::MAX_COMB_TEETH {
+                return Err(TableError::CombTeeth(comb_teeth));
+            }
+            let comb_len = ((1 << comb_teeth) - 1) * COMB_POINT_LEN;
+            let expected = HEADER_LEN + row_count * ROW_LEN + row_count * trials * (ENTRY_LEN + comb_len);
+            if bytes.len() != expected {
+                return Err(TableError::BadLength { expected, actual: bytes.len() });
+            }
+
+            let checksum = u32_at(bytes, 12);
+            let actual = crc32(&bytes[HEADER_LEN..]);
+            if actual != checksum {
+                return Err(TableError::Checksum { expected: checksum, actual });
+            }
+
+            Ok(Blob { bytes, row_count, trials, comb_teeth })
+        }
+
+        pub fn row(&self, size_idx: usize) -> (u16, u16) {
+            let offset = HEADER_LEN + size_idx * ROW_LEN;
+            (u16_at(self.bytes, offset), u16_at(self.bytes, offset + 2))
+        }
+
+        pub fn entry<const N: usize, const P: usize, const E: usize>(&self, idx: usize) -> Option<KeySize<N, P, E>> {
+            let start = HEADER_LEN + self.row_count * ROW_LEN + idx * ENTRY_LEN;
+            let mut c = Cursor { bytes: &self.bytes[start..start + ENTRY_LEN], pos: 0 };
+
+            // field order matters, it follows the struct definitions in lookup.rs
+            let ecc_bits = c.u16();
+            let rsa_bits = c.u16();
+            let rsa = RSA {
+                session_key: c.u2048()?,
+                exponent: c.u256()?,
+                p: c.u1024()?,
+                q: c.u1024()?,
+                d: c.u2048()?,
+                dp: c.u1024()?,
+                dq: c.u1024()?,
+                qinv: c.u1024()?,
+            };
+            let curve = EccCurve {
+                a: c.u256()?,
+                b: c.u256()?,
+                p: c.u256()?,
+                order: c.u256()?,
+                cofactor: c.u32(),
+                generator: Point { x: c.u256()?, y: c.u256()? },
+            };
+            let ecc = ECC { curve, private_key1: c.u256()?, private_key2: c.u256()? };
+            Some(KeySize { ecc_bits, rsa_bits, rsa, ecc })
+        }
+
+        // combs follow the entries, one per entry in the same order
+        pub fn comb_point<const E: usize>(&self, idx: usize, point: usize) -> Option<Point<E>> {
+            let points = (1 << self.comb_teeth) - 1;
+            if point >= points {
+                return None;
+            }
+            let combs = HEADER_LEN + self.row_count * ROW_LEN + self.row_count * self.trials * ENTRY_LEN;
+            let start = combs + (idx * points + point) * COMB_POINT_LEN;
+            let mut c = Cursor { bytes: &self.bytes[start..start + COMB_POINT_LEN], pos: 0 };
+            Some(Point { x: c.u256()?, y: c.u256()? })
+        }
+    }
+}
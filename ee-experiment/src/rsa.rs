@@ -1,20 +1,93 @@
-use crypto_bigint::{U256, U1024, U2048, NonZero, Limb};
+use core::str::FromStr;
+
+use crypto_bigint::{Uint, NonZero, Limb, Word};
 use crypto_bigint::modular::runtime_mod::{DynResidue, DynResidueParams};
+use crypto_bigint::subtle::{ConditionallySelectable, ConstantTimeEq};
+
+use crate::lookup::RSA;
+use crate::timing::{Clock, PhaseTimer};
+
+// windowed exponentiation keeps up to 2^6 powers on the stack, as Montgomery-form Uints
+pub const MAX_WINDOW: u8 = 6;
+const MAX_TABLE: usize = 1 << MAX_WINDOW;
+
+// how every modular exponentiation (encryption and decryption) is computed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Exponentiation {
+    // right-to-left square-and-multiply, a multiplication per set bit
+    SquareAndMultiply,
+    // left-to-right 2^w-ary, a multiplication per non-zero w-bit digit
+    KAry(u8),
+    // left-to-right over the odd powers, runs of zeros between windows are only squared
+    SlidingWindow(u8),
+    // constant time: every w-bit window of the modulus width multiplies, the table entry picked by
+    // masked selection over the whole table
+    FixedWindow(u8),
+    // crypto-bigint's own DynResidue::pow
+    Builtin,
+}
+
+// the names the host sends after START: square-and-multiply, k-ary:<w>, sliding:<w>, fixed:<w>
+// or builtin
+impl FromStr for Exponentiation {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s {
+            "square-and-multiply" => return Ok(Exponentiation::SquareAndMultiply),
+            "builtin" => return Ok(Exponentiation::Builtin),
+            _ => {}
+        }
+        let (method, width) = s.split_once(':').ok_or(())?;
+        let width: u8 = width.parse().map_err(|_| ())?;
+        if !(1..=MAX_WINDOW).contains(&width) {
+            return Err(());
+        }
+        match method {
+            "k-ary" => Ok(Exponentiation::KAry(width)),
+            "sliding" => Ok(Exponentiation::SlidingWindow(width)),
+            "fixed" => Ok(Exponentiation::FixedWindow(width)),
+            _ => Err(()),
+        }
+    }
+}
+
+// how c^d mod n is computed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Decryption {
+    // one exponentiation with d modulo the full n
+    Plain,
+    // c^dP mod p and c^dQ mod q over half-size moduli, recombined with Garner's formula
+    Crt,
+    // CRT, then re-encrypted and compared with c so a faulty half is never released
+    CrtChecked,
+}
 
-type Params2048 = DynResidueParams<{ U2048::LIMBS }>;
-type Residue2048 = DynResidue<{ U2048::LIMBS }>;
+// the names the host sends after START: plain, crt or crt-checked
+impl FromStr for Decryption {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s {
+            "plain" => Ok(Decryption::Plain),
+            "crt" => Ok(Decryption::Crt),
+            "crt-checked" => Ok(Decryption::CrtChecked),
+            _ => Err(()),
+        }
+    }
+}
 
 #[inline(always)]
-fn bit_vt(val: &U2048, bit: usize) -> bool {
+fn bit_vt<const N: usize>(val: &Uint<N>, bit: usize) -> bool {
     let li = bit / Limb::BITS;
     let bi = bit % Limb::BITS;
     (val.as_limbs()[li].0 >> bi) & 1 == 1
 }
 
 #[inline(always)]
-fn bitlen_vt(val: &U2048) -> usize {
+fn bitlen_vt<const N: usize>(val: &Uint<N>) -> usize {
     let limbs = val.as_limbs();
-    let mut i = U2048::LIMBS;
+    let mut i = N;
     while i > 0 {
         i -= 1;
         let w = limbs[i].0;
@@ -25,43 +98,29 @@ fn bitlen_vt(val: &U2048) -> usize {
     0
 }
 
-// widen U1024 -> U2048 by copying limbs
-fn widen_u1024(v: U1024) -> U2048 {
-    let src = v.as_limbs();
-    let mut limbs = [Limb::ZERO; U2048::LIMBS];
-    let mut i = 0;
-    while i < U1024::LIMBS {
-        limbs[i] = src[i];
-        i += 1;
-    }
-    U2048::from(limbs)
-}
-
-// widen U256 -> U2048
-fn widen_u256(v: U256) -> U2048 {
-    let src = v.as_limbs();
-    let mut limbs = [Limb::ZERO; U2048::LIMBS];
-    let mut i = 0;
-    while i < U256::LIMBS {
-        limbs[i] = src[i];
-        i += 1;
+// bits lo..lo + width of val as a number, bits past the top read as zero
+#[inline(always)]
+fn window_vt<const N: usize>(val: &Uint<N>, lo: usize, width: usize) -> usize {
+    let mut digit = 0;
+    for bit in (lo..(lo + width).min(Uint::<N>::BITS)).rev() {
+        digit = digit << 1 | bit_vt(val, bit) as usize;
     }
-    U2048::from(limbs)
+    digit
 }
 
 // extended GCD for modular inverse
 // can't use DynResidue here because totient is even
-fn extended_gcd(a: U2048, b: U2048) -> (U2048, U2048, bool) {
-    if b == U2048::ZERO { return (a, U2048::ONE, false); }
+fn extended_gcd<const N: usize>(a: Uint<N>, b: Uint<N>) -> (Uint<N>, Uint<N>, bool) {
+    if b == Uint::ZERO { return (a, Uint::ONE, false); }
 
     let mut old_r = a;
     let mut r = b;
-    let mut old_s = U2048::ONE;
-    let mut s = U2048::ZERO;
+    let mut old_s = Uint::ONE;
+    let mut s = Uint::ZERO;
     let mut old_s_neg = false;
     let mut s_neg = false;
 
-    while r != U2048::ZERO {
+    while r != Uint::ZERO {
         let nz_r = NonZero::new(r).unwrap();
         let (q, rem) = old_r.div_rem(&nz_r);
         old_r = r;
@@ -88,38 +147,86 @@ fn extended_gcd(a: U2048, b: U2048) -> (U2048, U2048, bool) {
     (old_r, old_s, old_s_neg)
 }
 
-fn mod_inv(a: U2048, m: U2048) -> U2048 {
+fn mod_inv<const N: usize>(a: Uint<N>, m: Uint<N>) -> Uint<N> {
     let (gcd, x, x_neg) = extended_gcd(a, m);
-    if gcd != U2048::ONE { crate::exit(); }
+    if gcd != Uint::ONE { crate::exit(); }
     let nz_m = NonZero::new(m).unwrap();
     let (_, rem) = x.div_rem(&nz_m);
     if x_neg { m.wrapping_sub(&rem) } else { rem }
 }
 
-pub struct RsaCtx {
-    params: Params2048,
-    totient: U2048,
+// N limbs for the modulus and everything mod n, P limbs for the primes
+pub struct RsaCtx<const N: usize, const P: usize> {
+    params: DynResidueParams<N>,
+    d: Uint<N>,
+    decryption: Decryption,
+    exponentiation: Exponentiation,
+    p_params: DynResidueParams<P>,
+    q_params: DynResidueParams<P>,
+    q: Uint<P>,
+    dp: Uint<P>,
+    dq: Uint<P>,
+    // q^-1 mod p
+    qinv: DynResidue<P>,
 }
 
-impl RsaCtx {
-    pub fn new(rsa: &crate::lookup::RSA) -> Self {
-        let p = widen_u1024(rsa.p);
-        let q = widen_u1024(rsa.q);
-        let n = p.wrapping_mul(&q);
-        let totient = p.wrapping_sub(&U2048::ONE).wrapping_mul(&q.wrapping_sub(&U2048::ONE));
-        let params = Params2048::new(&n);
-        RsaCtx { params, totient }
+impl<const N: usize, const P: usize> RsaCtx<N, P> {
+    // d and its CRT form come precomputed from primegen, nothing is inverted on the device
+    pub fn new(rsa: &RSA<N, P>, decryption: Decryption, exponentiation: Exponentiation) -> Self {
+        Self::from_parts(rsa, rsa.d, rsa.dp, rsa.dq, rsa.qinv, decryption, exponentiation)
+    }
+
+    // key generation from p, q and e as the device would do it, timed on its own
+    pub fn generate(rsa: &RSA<N, P>, decryption: Decryption, exponentiation: Exponentiation) -> Self {
+        let p = rsa.p.resize::<N>();
+        let q = rsa.q.resize::<N>();
+        let totient = p.wrapping_sub(&Uint::ONE).wrapping_mul(&q.wrapping_sub(&Uint::ONE));
+        let d = mod_inv(rsa.exponent, totient);
+
+        // the CRT exponents and q^-1 mod p only when the decryption uses them
+        if decryption == Decryption::Plain {
+            return Self::from_parts(rsa, d, Uint::ZERO, Uint::ZERO, Uint::ZERO, decryption, exponentiation);
+        }
+        let dp = d.rem(&NonZero::new(p.wrapping_sub(&Uint::ONE)).unwrap()).resize();
+        let dq = d.rem(&NonZero::new(q.wrapping_sub(&Uint::ONE)).unwrap()).resize();
+        let (qinv, _) = DynResidue::new(&rsa.q, DynResidueParams::new(&rsa.p)).invert();
+        Self::from_parts(rsa, d, dp, dq, qinv.retrieve(), decryption, exponentiation)
+    }
+
+    fn from_parts(
+        rsa: &RSA<N, P>,
+        d: Uint<N>,
+        dp: Uint<P>,
+        dq: Uint<P>,
+        qinv: Uint<P>,
+        decryption: Decryption,
+        exponentiation: Exponentiation,
+    ) -> Self {
+        let n = rsa.p.resize::<N>().wrapping_mul(&rsa.q.resize::<N>());
+        let p_params = DynResidueParams::new(&rsa.p);
+        RsaCtx {
+            params: DynResidueParams::new(&n),
+            d,
+            decryption,
+            exponentiation,
+            p_params,
+            q_params: DynResidueParams::new(&rsa.q),
+            q: rsa.q,
+            dp,
+            dq,
+            qinv: DynResidue::new(&qinv, p_params),
+        }
     }
 }
 
 // variable-time modular exponentiation (square-and-multiply)
-fn pow_vartime(base: Residue2048, exp: &U2048, params: Params2048) -> Residue2048 {
+fn pow_vartime<const N: usize>(base: DynResidue<N>, exp: &Uint<N>, params: DynResidueParams<N>) -> DynResidue<N> {
     let bits = bitlen_vt(exp);
     if bits == 0 {
-        return DynResidue::new(&U2048::ONE, params);
+        return DynResidue::new(&Uint::ONE, params);
     }
 
-    let mut result = DynResidue::new(&U2048::ONE, params);
+    let mut result = DynResidue::new(&Uint::ONE, params);
     let mut acc = base;
 
     for i in 0..bits {
@@ -131,17 +238,182 @@ fn pow_vartime(base: Residue2048, exp: &U2048, params: Params2048) -> Residue204
     result
 }
 
-pub fn key_transport(rsa: crate::lookup::RSA, ctx: &RsaCtx) -> bool {
-    let e = widen_u256(rsa.exponent);
+// base^0 .. base^(2^w - 1)
+fn power_table<const N: usize>(base: DynResidue<N>, width: u8) -> [Uint<N>; MAX_TABLE] {
+    let mut table = [Uint::ZERO; MAX_TABLE];
+    let mut power = DynResidue::one(*base.params());
+    for entry in table.iter_mut().take(1 << width) {
+        *entry = *power.as_montgomery();
+        power *= base;
+    }
+    table
+}
+
+// left-to-right 2^w-ary, starts from the top digit's table entry instead of squaring ones
+fn pow_k_ary<const N: usize>(base: DynResidue<N>, exp: &Uint<N>, width: u8) -> DynResidue<N> {
+    let params = *base.params();
+    let w = width as usize;
+    let windows = bitlen_vt(exp).div_ceil(w);
+    if windows == 0 {
+        return DynResidue::one(params);
+    }
+    let table = power_table(base, width);
+
+    let mut result = DynResidue::from_montgomery(table[window_vt(exp, (windows - 1) * w, w)], params);
+    for window in (0..windows - 1).rev() {
+        for _ in 0..w {
+            result = result.square();
+        }
+        let digit = window_vt(exp, window * w, w);
+        if digit != 0 {
+            result *= DynResidue::from_montgomery(table[digit], params);
+        }
+    }
+    result
+}
+
+// left-to-right sliding window, every window starts and ends on a set bit so only odd powers
+// base, base^3, .., base^(2^w - 1) are stored
+fn pow_sliding<const N: usize>(base: DynResidue<N>, exp: &Uint<N>, width: u8) -> DynResidue<N> {
+    let params = *base.params();
+    let w = width as usize;
+    let mut table = [Uint::ZERO; MAX_TABLE];
+    table[0] = *base.as_montgomery();
+    let squared = base.square();
+    for i in 1..1 << (w - 1) {
+        table[i] = *(DynResidue::from_montgomery(table[i - 1], params) * squared).as_montgomery();
+    }
+
+    let mut result = DynResidue::one(params);
+    let mut started = false;
+    // bits above `top` are done
+    let mut top = bitlen_vt(exp);
+    while top > 0 {
+        let i = top - 1;
+        if !bit_vt(exp, i) {
+            if started {
+                result = result.square();
+            }
+            top = i;
+            continue;
+        }
+        // longest window of at most w bits from i down that ends on a set bit
+        let mut j = (i + 1).saturating_sub(w);
+        while !bit_vt(exp, j) {
+            j += 1;
+        }
+        let odd = DynResidue::from_montgomery(table[window_vt(exp, j, i - j + 1) / 2], params);
+        if started {
+            for _ in j..=i {
+                result = result.square();
+            }
+            result *= odd;
+        } else {
+            result = odd;
+            started = true;
+        }
+        top = j;
+    }
+    result
+}
 
-    let d = mod_inv(e, ctx.totient);
+// fixed window in constant time: the loop covers the modulus width whatever the exponent is,
+// every window squares w times and multiplies once, and the entry is selected by masks over the
+// whole table so the memory access pattern does not depend on the digit either
+fn pow_fixed<const N: usize>(base: DynResidue<N>, exp: &Uint<N>, width: u8) -> DynResidue<N> {
+    let params = *base.params();
+    let w = width as usize;
+    let table = power_table(base, width);
+
+    let mut result = DynResidue::one(params);
+    for window in (0..bitlen_vt(params.modulus()).div_ceil(w)).rev() {
+        for _ in 0..w {
+            result = result.square();
+        }
+        let digit = window_vt(exp, window * w, w) as Word;
+        let mut entry = Uint::ZERO;
+        for (i, candidate) in table.iter().take(1 << w).enumerate() {
+            entry = Uint::conditional_select(&entry, candidate, (i as Word).ct_eq(&digit));
+        }
+        result *= DynResidue::from_montgomery(entry, params);
+    }
+    result
+}
+
+fn pow<const N: usize>(base: DynResidue<N>, exp: &Uint<N>, params: DynResidueParams<N>, method: Exponentiation) -> DynResidue<N> {
+    match method {
+        Exponentiation::SquareAndMultiply => pow_vartime(base, exp, params),
+        Exponentiation::KAry(width) => pow_k_ary(base, exp, width),
+        Exponentiation::SlidingWindow(width) => pow_sliding(base, exp, width),
+        Exponentiation::FixedWindow(width) => pow_fixed(base, exp, width),
+        Exponentiation::Builtin => base.pow(exp),
+    }
+}
+
+// c mod p for c below n = p q, as a residue mod p
+fn reduce<const N: usize, const P: usize>(c: &Uint<N>, params: DynResidueParams<P>) -> DynResidue<P> {
+    let modulus = params.modulus().resize::<N>();
+    DynResidue::new(&c.rem(&NonZero::new(modulus).unwrap()).resize(), params)
+}
+
+// m = m2 + q * (qInv (m1 - m2) mod p) with m1 = c^dP mod p and m2 = c^dQ mod q
+fn decrypt_crt<const N: usize, const P: usize>(c: &Uint<N>, ctx: &RsaCtx<N, P>) -> Uint<N> {
+    let m1 = pow(reduce(c, ctx.p_params), &ctx.dp, ctx.p_params, ctx.exponentiation);
+    let m2 = pow(reduce(c, ctx.q_params), &ctx.dq, ctx.q_params, ctx.exponentiation);
+    let m2_mod_p = reduce(&m2.retrieve(), ctx.p_params);
+    let h = (ctx.qinv * (m1 - m2_mod_p)).retrieve().resize::<N>();
+    m2.retrieve().resize::<N>().wrapping_add(&h.wrapping_mul(&ctx.q.resize::<N>()))
+}
+
+// where key_transport's cycles go, indices into its PhaseTimer
+#[derive(Clone, Copy)]
+pub enum RsaPhase {
+    // d (and its CRT form) derived from p, q and e, or only the Montgomery setup when taken from
+    // the table
+    Exponent,
+    // c = session_key^e mod n
+    Encryption,
+    // m = c^d mod n, including the re-encryption of crt-checked
+    Decryption,
+}
+pub const RSA_PHASES: usize = 3;
+
+// encrypts and decrypts the session key, what comes back is checked by `recovered`. None when the
+// checked CRT path caught a fault
+pub fn key_transport<const N: usize, const P: usize, C: Clock>(
+    rsa: &RSA<N, P>,
+    decryption: Decryption,
+    exponentiation: Exponentiation,
+    derive: bool,
+    timer: &mut PhaseTimer<C, RSA_PHASES>,
+) -> Option<Uint<N>> {
+    let ctx = if derive {
+        RsaCtx::generate(rsa, decryption, exponentiation)
+    } else {
+        RsaCtx::new(rsa, decryption, exponentiation)
+    };
+    timer.lap(RsaPhase::Exponent as usize);
 
     // encrypt: c = session_key^e mod n
     let base = DynResidue::new(&rsa.session_key, ctx.params);
-    let encrypted = pow_vartime(base, &e, ctx.params);
+    let encrypted = pow(base, &rsa.exponent, ctx.params, ctx.exponentiation);
+    timer.lap(RsaPhase::Encryption as usize);
 
     // decrypt: m = c^d mod n
-    let decrypted = pow_vartime(encrypted, &d, ctx.params);
+    let decrypted = match ctx.decryption {
+        Decryption::Plain => Some(pow(encrypted, &ctx.d, ctx.params, ctx.exponentiation).retrieve()),
+        Decryption::Crt => Some(decrypt_crt(&encrypted.retrieve(), &ctx)),
+        Decryption::CrtChecked => {
+            let c = encrypted.retrieve();
+            let m = decrypt_crt(&c, &ctx);
+            let check = pow(DynResidue::new(&m, ctx.params), &rsa.exponent, ctx.params, ctx.exponentiation);
+            if check.retrieve() == c { Some(m) } else { None }
+        }
+    };
+    timer.lap(RsaPhase::Decryption as usize);
+    decrypted
+}
 
-    decrypted.retrieve() == rsa.session_key
-}
\ No newline at end of file
+pub fn recovered<const N: usize, const P: usize>(decrypted: Option<Uint<N>>, rsa: &RSA<N, P>) -> bool {
+    decrypted == Some(rsa.session_key)
+}
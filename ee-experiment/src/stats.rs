@@ -0,0 +1,21 @@
+use ee_protocol::Summary;
+
+// sorts the samples in place, so nothing is allocated
+pub fn summarize(samples: &mut [u64]) -> Summary {
+    samples.sort_unstable();
+    let n = samples.len();
+    let median = if n % 2 == 1 {
+        samples[n / 2]
+    } else {
+        (samples[n / 2 - 1] + samples[n / 2]) / 2
+    };
+
+    // n * sum(x^2) - sum(x)^2 = n * (n - 1) * variance, exact in u128 for any realistic count
+    let (sum, sum_sq) = samples
+        .iter()
+        .fold((0u128, 0u128), |(sum, sum_sq), &x| (sum + x as u128, sum_sq + x as u128 * x as u128));
+    let n = n as u128;
+    let stddev = if n > 1 { ((n * sum_sq - sum * sum) / (n * (n - 1))).isqrt() as u64 } else { 0 };
+
+    Summary { min: samples[0], max: samples[samples.len() - 1], median, stddev }
+}
@@ -0,0 +1,155 @@
+use core::ops::{Add, AddAssign};
+
+use cortex_m::peripheral::{DWT, NVIC, SCB};
+use rp235x_hal::timer::{CopyableTimer0, Timer};
+
+// a point in time on a Clock, both counts from when the clock was made
+#[derive(Clone, Copy)]
+pub struct Instant {
+    pub cycles: u64,
+    pub micros: u64,
+    // the cycle count agreed with the microseconds when read
+    in_step: bool,
+}
+
+impl Instant {
+    pub fn new(cycles: u64, micros: u64, in_step: bool) -> Self {
+        Instant { cycles, micros, in_step }
+    }
+
+    pub fn since(&self, earlier: &Instant) -> Elapsed {
+        Elapsed {
+            cycles: self.cycles.wrapping_sub(earlier.cycles),
+            micros: self.micros.wrapping_sub(earlier.micros),
+            overflowed: !self.in_step || !earlier.in_step,
+        }
+    }
+}
+
+// a measurement, or the sum of several
+#[derive(Clone, Copy, Default)]
+pub struct Elapsed {
+    pub cycles: u64,
+    pub micros: u64,
+    // the cycle count of some measurement could not be trusted, see TimerClock
+    pub overflowed: bool,
+}
+
+impl Add for Elapsed {
+    type Output = Elapsed;
+
+    fn add(self, rhs: Elapsed) -> Elapsed {
+        Elapsed {
+            cycles: self.cycles + rhs.cycles,
+            micros: self.micros + rhs.micros,
+            overflowed: self.overflowed || rhs.overflowed,
+        }
+    }
+}
+
+impl AddAssign for Elapsed {
+    fn add_assign(&mut self, rhs: Elapsed) {
+        *self = *self + rhs;
+    }
+}
+
+pub trait Clock {
+    fn now(&self) -> Instant;
+}
+
+// CYCCNT is only 32 bits and wraps after ~28 s at 150 MHz. The RP2350's 64-bit 1 MHz timer runs
+// off the same reference, so it tells how many cycles have passed to within a microsecond and
+// the counter supplies the exact low 32 bits: the cycle count is the one congruent to CYCCNT
+// that is nearest to the timer's estimate. No interrupt is needed, so this works with interrupts
+// masked and over any length of run.
+pub struct TimerClock<'a> {
+    dwt: &'a DWT,
+    timer: Timer<CopyableTimer0>,
+    cycles_per_us: u64,
+    base_cycles: u32,
+    base_micros: u64,
+}
+
+// how far (in cycles) CYCCNT may be off the timer's estimate besides a microsecond of timer
+// resolution: the reads in between. Anything else means the counter stopped or skipped
+// (a debugger halt, a clock change) and how often it wrapped is unknown
+const SLACK_CYCLES: u64 = 64;
+
+impl<'a> TimerClock<'a> {
+    // the timer must already tick at 1 MHz, i.e. be created after the clocks are set up
+    pub fn new(dwt: &'a DWT, timer: Timer<CopyableTimer0>, sys_hz: u32) -> Self {
+        let base_micros = timer.get_counter().ticks();
+        TimerClock {
+            dwt,
+            timer,
+            cycles_per_us: (sys_hz / 1_000_000) as u64,
+            base_cycles: dwt.cyccnt.read(),
+            base_micros,
+        }
+    }
+}
+
+impl Clock for TimerClock<'_> {
+    #[inline(always)]
+    fn now(&self) -> Instant {
+        let micros = self.timer.get_counter().ticks().wrapping_sub(self.base_micros);
+        let low = self.dwt.cyccnt.read().wrapping_sub(self.base_cycles);
+        let estimate = micros * self.cycles_per_us;
+        let ahead = low.wrapping_sub(estimate as u32) as i32 as i64;
+        let in_step = ahead.unsigned_abs() < self.cycles_per_us + SLACK_CYCLES;
+        Instant::new(estimate.wrapping_add(ahead as u64), micros, in_step)
+    }
+}
+
+// splits a timed run into K phases: restart right before the run, lap at the end of each phase.
+// time adds up over runs
+pub struct PhaseTimer<'a, C: Clock, const K: usize> {
+    clock: &'a C,
+    last: Instant,
+    phases: [Elapsed; K],
+}
+
+impl<'a, C: Clock, const K: usize> PhaseTimer<'a, C, K> {
+    pub fn new(clock: &'a C) -> Self {
+        PhaseTimer { clock, last: clock.now(), phases: [Elapsed::default(); K] }
+    }
+
+    #[inline(always)]
+    pub fn restart(&mut self) {
+        self.last = self.clock.now();
+    }
+
+    // charges the time since the previous lap or restart to `phase`
+    #[inline(always)]
+    pub fn lap(&mut self, phase: usize) {
+        let now = self.clock.now();
+        self.phases[phase] += now.since(&self.last);
+        self.last = now;
+    }
+
+    pub fn total(&self, phase: usize) -> Elapsed {
+        self.phases[phase]
+    }
+}
+
+// runs `f` with interrupts masked on this core and counts the interrupts raised meanwhile: the
+// enabled NVIC lines and SysTick that went pending, each of which would have run inside the
+// measurement and runs right after it instead. A line raised twice counts once
+pub fn masked<R>(f: impl FnOnce() -> R) -> (R, u32) {
+    cortex_m::interrupt::free(|_| {
+        let before = pending_interrupts();
+        let result = f();
+        (result, (pending_interrupts() & !before).count_ones())
+    })
+}
+
+// bits 0-51 for the RP2350's NVIC lines, bit 63 for SysTick
+fn pending_interrupts() -> u64 {
+    // only read, these registers are no driver's
+    let (nvic, scb) = unsafe { (&*NVIC::PTR, &*SCB::PTR) };
+    let lines = (0..2).fold(0u64, |pending, i| {
+        pending | ((nvic.ispr[i].read() & nvic.iser[i].read()) as u64) << (32 * i)
+    });
+    let systick = (scb.icsr.read() >> 26) & 1;
+    lines | (systick as u64) << 63
+}
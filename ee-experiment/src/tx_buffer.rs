@@ -0,0 +1,92 @@
+// bytes on their way to the host: written whole by core 1's interrupt handlers as core 0's packets
+// arrive, taken out again as fast as the host reads them
+
+pub struct TxBuffer<const N: usize> {
+    buf: [u8; N],
+    // oldest byte
+    start: usize,
+    len: usize,
+}
+
+impl<const N: usize> TxBuffer<N> {
+    pub const fn new() -> Self {
+        TxBuffer { buf: [0; N], start: 0, len: 0 }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // all of `bytes` or none of them, so a frame or a line is never cut
+    pub fn push(&mut self, bytes: &[u8]) -> bool {
+        if bytes.len() > N - self.len {
+            return false;
+        }
+        for &byte in bytes {
+            self.buf[(self.start + self.len) % N] = byte;
+            self.len += 1;
+        }
+        true
+    }
+
+    // the oldest bytes, up to the end of the ring
+    pub fn pending(&self) -> &[u8] {
+        let end = (self.start + self.len).min(N);
+        &self.buf[self.start..end]
+    }
+
+    // the first `count` bytes of pending() are out
+    pub fn consume(&mut self, count: usize) {
+        let count = count.min(self.len);
+        self.start = (self.start + count) % N;
+        self.len -= count;
+    }
+}
+
+#[cfg(all(test, host_test))]
+mod tests {
+    use super::*;
+
+    // everything pending, the way core 1 writes it out `chunk` bytes at a time
+    fn drain<const N: usize>(buffer: &mut TxBuffer<N>, chunk: usize) -> Vec<u8> {
+        let mut out = Vec::new();
+        while !buffer.is_empty() {
+            let count = buffer.pending().len().min(chunk);
+            out.extend_from_slice(&buffer.pending()[..count]);
+            buffer.consume(count);
+        }
+        out
+    }
+
+    #[test]
+    fn in_order_across_the_wrap() {
+        let mut buffer = TxBuffer::<8>::new();
+        for round in 0..10u8 {
+            assert!(buffer.push(&[round, round + 1, round + 2, round + 3, round + 4]));
+            assert_eq!(drain(&mut buffer, 3), [round, round + 1, round + 2, round + 3, round + 4]);
+        }
+    }
+
+    #[test]
+    fn pending_stops_at_the_end_of_the_ring() {
+        let mut buffer = TxBuffer::<8>::new();
+        assert!(buffer.push(b"abcdef"));
+        buffer.consume(4);
+        assert!(buffer.push(b"ghij"));
+        assert_eq!(buffer.pending(), b"efgh");
+        buffer.consume(4);
+        assert_eq!(buffer.pending(), b"ij");
+    }
+
+    #[test]
+    fn whole_writes_only() {
+        let mut buffer = TxBuffer::<8>::new();
+        assert!(buffer.push(b"12345"));
+        assert!(!buffer.push(b"6789"));
+        assert!(buffer.push(b"678"));
+        assert!(!buffer.push(b"9"));
+        assert_eq!(drain(&mut buffer, 8), b"12345678");
+        assert!(!buffer.push(b"123456789"));
+        assert!(buffer.is_empty());
+    }
+}
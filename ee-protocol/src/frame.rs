@@ -0,0 +1,195 @@
+// a frame is the postcard-serialized message followed by its CRC-16 (little endian), COBS-encoded
+// so that it contains no zero byte, then a zero byte ending it. A frame that does not decode, is
+// cut short or fails the CRC is reported as such, never repaired
+
+use core::fmt;
+
+use crc::{Crc, CRC_16_IBM_3740};
+
+use crate::Message;
+
+// CRC-16/CCITT-FALSE
+const CRC: Crc<u16> = Crc::<u16>::new(&CRC_16_IBM_3740);
+
+// the largest serialized message, a trial result with every field at its maximum, is ~215 bytes
+const MAX_PAYLOAD: usize = 256;
+// payload and CRC after COBS, and the zero byte
+pub const MAX_FRAME: usize = cobs::max_encoding_length(MAX_PAYLOAD + 2) + 1;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FrameError {
+    // the message does not fit MAX_PAYLOAD
+    TooLong,
+    // not valid COBS, bytes were lost or inserted
+    Cobs,
+    // fewer bytes than a CRC
+    TooShort,
+    Crc { expected: u16, actual: u16 },
+    // the CRC matched but the bytes are not a message, e.g. from another protocol version
+    Message,
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FrameError::TooLong => write!(f, "message longer than {} bytes", MAX_PAYLOAD),
+            FrameError::Cobs => write!(f, "invalid COBS encoding"),
+            FrameError::TooShort => write!(f, "frame shorter than its CRC"),
+            FrameError::Crc { expected, actual } => {
+                write!(f, "CRC mismatch (frame says {:04x}, contents give {:04x})", expected, actual)
+            }
+            FrameError::Message => write!(f, "CRC matches but the contents are not a message"),
+        }
+    }
+}
+
+// the whole frame including the closing zero byte, in the front of `out`
+pub fn encode<'b>(message: &Message, out: &'b mut [u8; MAX_FRAME]) -> Result<&'b [u8], FrameError> {
+    let mut raw = [0u8; MAX_PAYLOAD + 2];
+    let len = postcard::to_slice(message, &mut raw[..MAX_PAYLOAD]).map_err(|_| FrameError::TooLong)?.len();
+    let crc = CRC.checksum(&raw[..len]);
+    raw[len..len + 2].copy_from_slice(&crc.to_le_bytes());
+
+    let encoded = cobs::encode(&raw[..len + 2], &mut out[..MAX_FRAME - 1]);
+    out[encoded] = 0;
+    Ok(&out[..encoded + 1])
+}
+
+// one frame without its closing zero byte, decoded in place: string fields borrow from it
+pub fn decode(frame: &mut [u8]) -> Result<Message<'_>, FrameError> {
+    let len = cobs::decode_in_place(frame).map_err(|_| FrameError::Cobs)?;
+    if len < 2 {
+        return Err(FrameError::TooShort);
+    }
+    let (payload, crc) = frame[..len].split_at(len - 2);
+    let expected = u16::from_le_bytes([crc[0], crc[1]]);
+    let actual = CRC.checksum(payload);
+    if expected != actual {
+        return Err(FrameError::Crc { expected, actual });
+    }
+    match postcard::take_from_bytes(payload) {
+        Ok((message, [])) => Ok(message),
+        _ => Err(FrameError::Message),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{RawSample, RunHeader, SizeHeader, Summary, TrialResult};
+
+    const TRIAL: TrialResult = TrialResult {
+        trial: 3,
+        ecc_cycles: 1_234_567,
+        rsa_cycles: u64::MAX,
+        ecc_fails: 0,
+        rsa_fails: 2,
+        ecc_micros: 8_230,
+        rsa_micros: 0,
+        keygen: Some([40_000, 9_000_000]),
+        ecc_phases: [100, 200, 300],
+        rsa_phases: [0, 5_000, u64::MAX],
+        summary: Some([Summary { min: 1, max: 2, median: 1, stddev: 0 }; 2]),
+        interrupts: [0, u32::MAX],
+        overflowed: true,
+    };
+
+    // the frame without its closing zero byte, which has to be its only zero
+    fn frame(message: &Message, out: &mut [u8; MAX_FRAME]) -> usize {
+        let frame = encode(message, out).unwrap();
+        let (last, body) = frame.split_last().unwrap();
+        assert_eq!(*last, 0);
+        assert!(!body.contains(&0));
+        body.len()
+    }
+
+    #[test]
+    fn every_message_round_trips() {
+        let messages = [
+            Message::RunHeader(RunHeader {
+                coordinates: "Jacobian",
+                scalar_mult: "Wnaf(4)",
+                comb_teeth: 4,
+                decryption: "CrtChecked",
+                exponentiation: "Sliding(5)",
+                raw: true,
+                summary: false,
+                iterations: 64,
+            }),
+            Message::SizeHeader(SizeHeader { index: 12, ecc_bits: 208, rsa_bits: 1806 }),
+            Message::TrialResult(TRIAL),
+            Message::TrialResult(TrialResult { keygen: None, summary: None, ..TRIAL }),
+            Message::RawSample(RawSample { size: 1, trial: 2, iteration: 15, ecc_cycles: 42, rsa_cycles: 1 << 40 }),
+            Message::Error("Run aborted by the host"),
+            Message::Complete,
+            Message::Ack("PING"),
+            Message::Nak("SET iterations 0: iterations must be 1 to 64"),
+        ];
+        for message in messages {
+            let mut out = [0; MAX_FRAME];
+            let len = frame(&message, &mut out);
+            assert_eq!(decode(&mut out[..len]), Ok(message));
+        }
+    }
+
+    #[test]
+    fn zero_bytes_in_the_payload() {
+        let message = Message::SizeHeader(SizeHeader { index: 0, ecc_bits: 0, rsa_bits: 0 });
+        let mut raw = [0xff; MAX_PAYLOAD];
+        assert!(postcard::to_slice(&message, &mut raw).unwrap().contains(&0));
+
+        let mut out = [0; MAX_FRAME];
+        let len = frame(&message, &mut out);
+        assert_eq!(decode(&mut out[..len]), Ok(message));
+
+        let message = Message::Error("a\0b\0\0");
+        let len = frame(&message, &mut out);
+        assert_eq!(decode(&mut out[..len]), Ok(message));
+    }
+
+    #[test]
+    fn flipped_payload_byte_fails_the_crc() {
+        let mut raw = [0; MAX_PAYLOAD + 2];
+        let len = postcard::to_slice(&Message::TrialResult(TRIAL), &mut raw).unwrap().len();
+        let crc = CRC.checksum(&raw[..len]);
+        raw[len..len + 2].copy_from_slice(&crc.to_le_bytes());
+        raw[len / 2] ^= 0x10;
+
+        let mut out = [0; MAX_FRAME];
+        let encoded = cobs::encode(&raw[..len + 2], &mut out);
+        assert_eq!(
+            decode(&mut out[..encoded]),
+            Err(FrameError::Crc { expected: crc, actual: CRC.checksum(&raw[..len]) })
+        );
+    }
+
+    #[test]
+    fn truncated_frames() {
+        let mut out = [0; MAX_FRAME];
+        let len = frame(&Message::TrialResult(TRIAL), &mut out);
+        for cut in 1..len {
+            let mut truncated = out;
+            assert!(decode(&mut truncated[..len - cut]).is_err(), "cut by {}", cut);
+        }
+
+        let encoded = cobs::encode(&[0x42], &mut out);
+        assert_eq!(decode(&mut out[..encoded]), Err(FrameError::TooShort));
+    }
+
+    #[test]
+    fn over_long_messages_and_frames() {
+        let long = [b'x'; MAX_PAYLOAD];
+        let message = Message::Error(core::str::from_utf8(&long).unwrap());
+        assert_eq!(encode(&message, &mut [0; MAX_FRAME]), Err(FrameError::TooLong));
+
+        // a message with bytes after it, under a CRC that covers them
+        let mut raw = [0; MAX_PAYLOAD + 2];
+        let len = postcard::to_slice(&Message::Complete, &mut raw).unwrap().len() + 1;
+        raw[len - 1] = 7;
+        let crc = CRC.checksum(&raw[..len]);
+        raw[len..len + 2].copy_from_slice(&crc.to_le_bytes());
+        let mut out = [0; MAX_FRAME];
+        let encoded = cobs::encode(&raw[..len + 2], &mut out);
+        assert_eq!(decode(&mut out[..encoded]), Err(FrameError::Message));
+    }
+}
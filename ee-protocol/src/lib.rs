@@ -0,0 +1,175 @@
+#![no_std]
+
+// what ee-experiment reports, either rendered as the text lines below or framed (frame.rs) for
+// ee-decoder, which renders the same text on the host
+
+use core::fmt;
+
+use serde::{Deserialize, Serialize};
+
+mod frame;
+pub use frame::{decode, encode, FrameError, MAX_FRAME};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Message<'a> {
+    #[serde(borrow)]
+    RunHeader(RunHeader<'a>),
+    SizeHeader(SizeHeader),
+    TrialResult(TrialResult),
+    RawSample(RawSample),
+    Error(&'a str),
+    Complete,
+    // replies to host commands, the command line and what it returned
+    Ack(&'a str),
+    Nak(&'a str),
+}
+
+// the settings of the run, the method names as the firmware prints them
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RunHeader<'a> {
+    pub coordinates: &'a str,
+    pub scalar_mult: &'a str,
+    // 0 without the generator comb
+    pub comb_teeth: u16,
+    pub decryption: &'a str,
+    pub exponentiation: &'a str,
+    pub raw: bool,
+    pub summary: bool,
+    // per trial, from SET iterations
+    pub iterations: u16,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SizeHeader {
+    // row of the parameter table
+    pub index: u16,
+    pub ecc_bits: u16,
+    pub rsa_bits: u16,
+}
+
+// one trial, cycles averaged over its iterations
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TrialResult {
+    // from 1
+    pub trial: u16,
+    pub ecc_cycles: u64,
+    pub rsa_cycles: u64,
+    pub ecc_fails: u16,
+    pub rsa_fails: u16,
+    pub ecc_micros: u64,
+    pub rsa_micros: u64,
+    // ECC and RSA key generation, with keygen-timing
+    pub keygen: Option<[u64; 2]>,
+    // setup, keygen, shared secret
+    pub ecc_phases: [u64; 3],
+    // exponent, encryption, decryption
+    pub rsa_phases: [u64; 3],
+    // ECC and RSA, with START ... summary
+    pub summary: Option<[Summary; 2]>,
+    // ECC and RSA, interrupts raised during the trial's measurements and held off until each was over
+    pub interrupts: [u32; 2],
+    // the cycle counter fell out of step with the timer during the trial
+    pub overflowed: bool,
+}
+
+// spread of one trial's iterations, all in cycles
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Summary {
+    pub min: u64,
+    pub max: u64,
+    pub median: u64,
+    // sample standard deviation, rounded down
+    pub stddev: u64,
+}
+
+// one iteration's exchange windows, with START ... raw
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RawSample {
+    pub size: u16,
+    // both from 1
+    pub trial: u16,
+    pub iteration: u16,
+    pub ecc_cycles: u64,
+    pub rsa_cycles: u64,
+}
+
+// the text lines data-receiver/parser.py reads, every message ends its last line
+impl fmt::Display for Message<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Message::RunHeader(header) => {
+                writeln!(f, "=== Started EE Experiment ===")?;
+                writeln!(f, "ECC coordinates: {}", header.coordinates)?;
+                writeln!(f, "ECC scalar multiplication: {}", header.scalar_mult)?;
+                writeln!(f, "ECC generator comb: {} teeth", header.comb_teeth)?;
+                writeln!(f, "RSA decryption: {}", header.decryption)?;
+                writeln!(f, "RSA exponentiation: {}", header.exponentiation)?;
+                writeln!(f, "Iterations per trial: {}", header.iterations)?;
+                writeln!(
+                    f,
+                    "Output: averages{}{}",
+                    if header.raw { ", raw samples" } else { "" },
+                    if header.summary { ", summary" } else { "" }
+                )
+            }
+            // rows are separated by an empty line
+            Message::SizeHeader(size) => {
+                if size.index > 0 {
+                    writeln!(f)?;
+                }
+                writeln!(f, "=== ECC {} / RSA {} bits ===", size.ecc_bits, size.rsa_bits)
+            }
+            Message::TrialResult(result) => {
+                if result.overflowed {
+                    writeln!(
+                        f,
+                        "[ERROR] Cycle counter out of step with the timer, cycle counts of trial #{} are unreliable",
+                        result.trial
+                    )?;
+                }
+                write!(
+                    f,
+                    "Trial #{}: ECC = {}, RSA = {}, ECC fails = {}, RSA fails = {}, ECC us = {}, RSA us = {}",
+                    result.trial,
+                    result.ecc_cycles,
+                    result.rsa_cycles,
+                    result.ecc_fails,
+                    result.rsa_fails,
+                    result.ecc_micros,
+                    result.rsa_micros
+                )?;
+                if let Some([ecc, rsa]) = result.keygen {
+                    write!(f, ", ECC keygen = {}, RSA keygen = {}", ecc, rsa)?;
+                }
+                let [setup, keygen, secret] = result.ecc_phases;
+                let [exponent, encryption, decryption] = result.rsa_phases;
+                let [ecc_interrupts, rsa_interrupts] = result.interrupts;
+                writeln!(
+                    f,
+                    ", ECC phases = {}/{}/{}, RSA phases = {}/{}/{}, ECC interrupts = {}, RSA interrupts = {}",
+                    setup, keygen, secret, exponent, encryption, decryption, ecc_interrupts, rsa_interrupts
+                )?;
+                if let Some([ecc, rsa]) = result.summary {
+                    writeln!(
+                        f,
+                        "Summary #{}: ECC min/max/median/stddev = {}/{}/{}/{}, RSA min/max/median/stddev = {}/{}/{}/{}",
+                        result.trial, ecc.min, ecc.max, ecc.median, ecc.stddev, rsa.min, rsa.max, rsa.median, rsa.stddev
+                    )?;
+                }
+                Ok(())
+            }
+            Message::RawSample(sample) => writeln!(
+                f,
+                "Sample {}/{}/{}: ECC = {}, RSA = {}",
+                sample.size, sample.trial, sample.iteration, sample.ecc_cycles, sample.rsa_cycles
+            ),
+            Message::Error(message) => writeln!(f, "[ERROR] {}", message),
+            Message::Complete => {
+                writeln!(f)?;
+                writeln!(f, "=== Experiment Complete ===")
+            }
+            Message::Ack(reply) => writeln!(f, "OK {}", reply),
+            Message::Nak(reply) => writeln!(f, "ERR {}", reply),
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use num_bigint::BigUint;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::curve::{CURVE_A, CURVE_B};
+use crate::TrialResult;
+
+// binary parameter table, read by ee-experiment/src/params.rs
+//
+// everything is little-endian, integers are fixed-width runs of 64-bit limbs
+//
+// header (20 bytes):
+//   magic "EEPT", version u16, row_count u16, trials u16, entry_len u16,
+//   checksum u32 (CRC-32/IEEE of everything after the header), comb_teeth u16, reserved u16
+// rows: row_count x (ecc_bits u16, rsa_bits u16)
+// entries: row_count * trials KeySize records, grouped by row, fields in lookup.rs order
+//   ecc_bits u16, rsa_bits u16,
+//   rsa: session_key U2048, exponent U256, p U1024, q U1024, d U2048, dp, dq, qinv U1024,
+//   ecc: a, b, p, order U256, cofactor u32, generator x, y U256, private_key1, private_key2 U256
+// combs: row_count * trials generator combs in entry order, 2^comb_teeth - 1 points of x, y U256
+const MAGIC: [u8; 4] = *b"EEPT";
+const VERSION: u16 = 3;
+
+const U256_BYTES: usize = 32;
+const U1024_BYTES: usize = 128;
+const U2048_BYTES: usize = 256;
+const ENTRY_LEN: usize = 2 + 2 + 2 * U2048_BYTES + U256_BYTES + 5 * U1024_BYTES + 8 * U256_BYTES + 4;
+
+// CRC-32 (IEEE 802.3, reflected), bitwise to match the firmware
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+fn push_uint(out: &mut Vec<u8>, v: &BigUint, width: usize) {
+    let bytes = v.to_bytes_le();
+    assert!(bytes.len() <= width, "Value too large for {} bytes: {:x}", width, v);
+    out.extend_from_slice(&bytes);
+    out.resize(out.len() + width - bytes.len(), 0);
+}
+
+fn encode_entry(out: &mut Vec<u8>, e: &TrialResult) {
+    let start = out.len();
+    out.extend_from_slice(&e.ecc_bits.to_le_bytes());
+    out.extend_from_slice(&e.rsa_bits.to_le_bytes());
+    push_uint(out, &e.session_key, U2048_BYTES);
+    push_uint(out, &e.exponent, U256_BYTES);
+    push_uint(out, &e.p, U1024_BYTES);
+    push_uint(out, &e.q, U1024_BYTES);
+    push_uint(out, &e.d, U2048_BYTES);
+    push_uint(out, &e.dp, U1024_BYTES);
+    push_uint(out, &e.dq, U1024_BYTES);
+    push_uint(out, &e.qinv, U1024_BYTES);
+    push_uint(out, &BigUint::from(CURVE_A), U256_BYTES);
+    push_uint(out, &BigUint::from(CURVE_B), U256_BYTES);
+    push_uint(out, &e.ecc_prime, U256_BYTES);
+    push_uint(out, &e.ecc_order, U256_BYTES);
+    out.extend_from_slice(&e.ecc_cofactor.to_le_bytes());
+    push_uint(out, &e.gen_x, U256_BYTES);
+    push_uint(out, &e.gen_y, U256_BYTES);
+    push_uint(out, &e.ecc_private_key1, U256_BYTES);
+    push_uint(out, &e.ecc_private_key2, U256_BYTES);
+    debug_assert_eq!(out.len() - start, ENTRY_LEN);
+}
+
+// entries must be grouped by row in `rows` order, `trials` per row
+pub fn write_blob(path: &Path, rows: &[(u16, u16)], trials: u8, comb_teeth: u8, entries: &[&TrialResult]) -> io::Result<()> {
+    assert_eq!(entries.len(), rows.len() * trials as usize, "Entry count does not match rows x trials");
+
+    let comb_len = ((1usize << comb_teeth) - 1) * 2 * U256_BYTES;
+    let mut body = Vec::with_capacity(rows.len() * 4 + entries.len() * (ENTRY_LEN + comb_len));
+    for &(ecc, rsa) in rows {
+        body.extend_from_slice(&ecc.to_le_bytes());
+        body.extend_from_slice(&rsa.to_le_bytes());
+    }
+    for e in entries {
+        encode_entry(&mut body, e);
+    }
+    for e in entries {
+        for (x, y) in &e.comb {
+            push_uint(&mut body, x, U256_BYTES);
+            push_uint(&mut body, y, U256_BYTES);
+        }
+    }
+    debug_assert_eq!(body.len(), rows.len() * 4 + entries.len() * (ENTRY_LEN + comb_len));
+
+    let mut w = BufWriter::new(File::create(path)?);
+    w.write_all(&MAGIC)?;
+    w.write_all(&VERSION.to_le_bytes())?;
+    w.write_all(&(rows.len() as u16).to_le_bytes())?;
+    w.write_all(&(trials as u16).to_le_bytes())?;
+    w.write_all(&(ENTRY_LEN as u16).to_le_bytes())?;
+    w.write_all(&crc32(&body).to_le_bytes())?;
+    w.write_all(&(comb_teeth as u16).to_le_bytes())?;
+    w.write_all(&0u16.to_le_bytes())?;
+    w.write_all(&body)?;
+    w.flush()
+}
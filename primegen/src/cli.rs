@@ -0,0 +1,127 @@
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+
+use crate::comb::MAX_COMB_TEETH;
+use crate::conversions::ECC_V_RSA;
+use crate::fips::RsaPrimes;
+use crate::is_prime::{Primality, PrimalityTest};
+use crate::sieve::PrimeSearch;
+
+const DEFAULT_TRIALS: u8 = 20;
+const DEFAULT_SEED: u32 = 873267326;
+const DEFAULT_SEARCH_LIMIT: u32 = 5_000_000;
+const DEFAULT_EXPONENT: u32 = 65537;
+
+// without a subcommand primegen generates, so the old `cargo run --release` keeps working
+#[derive(Parser, Debug)]
+#[command(version, about = "Generate the RSA/ECC parameter lookup table for the firmware")]
+#[command(args_conflicts_with_subcommands = true)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    #[command(flatten)]
+    pub generate: Args,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Generate a lookup table (the default)
+    Generate(Args),
+    /// Re-check every invariant of an existing lookup table
+    Verify(VerifyArgs),
+}
+
+#[derive(clap::Args, Debug)]
+pub struct VerifyArgs {
+    /// Lookup table to audit
+    #[arg(default_value = "./lookup.rs")]
+    pub path: PathBuf,
+}
+
+// generates the prime lookup table used by the ee-experiment firmware
+#[derive(clap::Args, Clone, Debug)]
+pub struct Args {
+    /// Trials generated per key-size row
+    #[arg(short, long, default_value_t = DEFAULT_TRIALS, value_parser = clap::value_parser!(u8).range(1..))]
+    pub trials: u8,
+
+    /// Seed every parameter stream is derived from
+    #[arg(short, long, default_value_t = DEFAULT_SEED)]
+    pub seed: u32,
+
+    /// Candidates tried before a prime or curve point search gives up
+    #[arg(long, default_value_t = DEFAULT_SEARCH_LIMIT, value_parser = clap::value_parser!(u32).range(1..))]
+    pub search_limit: u32,
+
+    /// How prime candidates are filtered before the primality test, both give the same table
+    #[arg(long, value_enum, default_value_t = PrimeSearch::Sieve)]
+    pub prime_search: PrimeSearch,
+
+    /// Also run every row with the other prime search and print both times and the sieve's speedup
+    #[arg(long)]
+    pub compare: bool,
+
+    /// Primality test used for every prime in the table
+    #[arg(long, value_enum, default_value_t = PrimalityTest::Bpsw)]
+    pub primality: PrimalityTest,
+
+    /// Extra Miller-Rabin rounds with random bases, derived from the seed and the candidate
+    #[arg(long, default_value_t = 0)]
+    pub extra_rounds: u32,
+
+    /// How the RSA primes p and q are drawn
+    #[arg(long, value_enum, default_value_t = RsaPrimes::Random)]
+    pub rsa_primes: RsaPrimes,
+
+    /// Teeth of the precomputed generator comb written per trial (2^t - 1 points), 0 leaves it out
+    #[arg(long, default_value_t = 0, value_parser = clap::value_parser!(u8).range(0..=MAX_COMB_TEETH as i64))]
+    pub comb_teeth: u8,
+
+    /// RSA public exponent
+    #[arg(short, long, default_value_t = DEFAULT_EXPONENT, value_parser = parse_exponent)]
+    pub exponent: u32,
+
+    /// Key-size rows to generate, by ECC bits (e.g. 16,32,48), defaults to the full table
+    #[arg(short, long, value_delimiter = ',', value_parser = parse_row)]
+    pub rows: Vec<(u16, u16)>,
+
+    /// Path of the generated lookup table, the binary blob is written next to it as .bin
+    #[arg(short, long, default_value = "./lookup.rs")]
+    pub output: PathBuf,
+}
+
+impl Args {
+    pub fn primality(&self) -> Primality {
+        Primality { test: self.primality, extra_rounds: self.extra_rounds, seed: self.seed }
+    }
+
+    // selected rows in table order, duplicates removed
+    pub fn rows(&self) -> Vec<(u16, u16)> {
+        ECC_V_RSA
+            .iter()
+            .copied()
+            .filter(|row| self.rows.is_empty() || self.rows.contains(row))
+            .collect()
+    }
+}
+
+fn parse_exponent(s: &str) -> Result<u32, String> {
+    let e: u32 = s.parse().map_err(|e| format!("{}", e))?;
+    if e < 3 || e.is_multiple_of(2) {
+        return Err(format!("{} is not an odd exponent >= 3", e));
+    }
+    Ok(e)
+}
+
+fn parse_row(s: &str) -> Result<(u16, u16), String> {
+    let ecc_bits: u16 = s.parse().map_err(|e| format!("{}", e))?;
+    ECC_V_RSA
+        .iter()
+        .copied()
+        .find(|&(ecc, _)| ecc == ecc_bits)
+        .ok_or_else(|| {
+            let sizes: Vec<String> = ECC_V_RSA.iter().map(|(ecc, _)| ecc.to_string()).collect();
+            format!("no {}-bit ECC row, expected one of {}", ecc_bits, sizes.join(","))
+        })
+}
@@ -0,0 +1,34 @@
+use num_bigint::BigUint;
+use num_traits::One;
+
+use crate::curve::{point_add, scalar_mul, Point};
+
+// the firmware keeps the whole comb in RAM, 2^6 - 1 points per trial is its limit
+pub const MAX_COMB_TEETH: u8 = 6;
+
+// bits of the scalar between two teeth, every scalar below the order is covered
+fn spacing(order: &BigUint, teeth: u8) -> u64 {
+    order.bits().div_ceil(teeth as u64)
+}
+
+// Lim-Lee comb for the generator: entry i - 1 is the sum of 2^(j * spacing) G over the set bits
+// j of i, so one addition per column replaces up to `teeth` of them. None if an entry is the
+// point at infinity, which only a generator of the wrong order allows
+pub fn comb_table(generator: &Point, order: &BigUint, teeth: u8, p: &BigUint) -> Option<Vec<(BigUint, BigUint)>> {
+    if teeth == 0 {
+        return Some(Vec::new());
+    }
+    let spacing = spacing(order, teeth);
+    let teeth_points: Vec<Point> =
+        (0..teeth as u64).map(|j| scalar_mul(&(BigUint::one() << (j * spacing)), generator, p)).collect();
+
+    (1usize..1 << teeth)
+        .map(|idx| {
+            teeth_points
+                .iter()
+                .enumerate()
+                .filter(|&(j, _)| idx >> j & 1 == 1)
+                .fold(None, |acc, (_, point)| point_add(&acc, point, p))
+        })
+        .collect()
+}
@@ -0,0 +1,121 @@
+use num_bigint::BigUint;
+use num_traits::{One, Zero};
+
+// curve: y^2 = x^3 + ax + b (mod p)
+pub const CURVE_A: u32 = 2;
+pub const CURVE_B: u32 = 3;
+
+// right-hand side of the curve equation, x^3 + ax + b mod p
+pub fn curve_rhs(x: &BigUint, p: &BigUint) -> BigUint {
+    let x3 = x.modpow(&BigUint::from(3u32), p);
+    (x3 + BigUint::from(CURVE_A) * x + BigUint::from(CURVE_B)) % p
+}
+
+pub fn is_on_curve(x: &BigUint, y: &BigUint, p: &BigUint) -> bool {
+    x < p && y < p && (y * y) % p == curve_rhs(x, p)
+}
+
+// Euler's criterion, n^((p-1)/2) == 1 for non-zero quadratic residues
+fn is_quadratic_residue(n: &BigUint, p: &BigUint) -> bool {
+    let exp = (p - BigUint::one()) >> 1usize;
+    n.modpow(&exp, p).is_one()
+}
+
+// modular square root for an odd prime p (Tonelli-Shanks)
+pub fn sqrt_mod(n: &BigUint, p: &BigUint) -> Option<BigUint> {
+    let n = n % p;
+    if n.is_zero() {
+        return Some(BigUint::zero());
+    }
+    if !is_quadratic_residue(&n, p) {
+        return None;
+    }
+
+    // p = 3 mod 4 shortcut (always the case for the safe primes we use)
+    let four = BigUint::from(4u32);
+    if p % &four == BigUint::from(3u32) {
+        let exp = (p + BigUint::one()) / &four;
+        return Some(n.modpow(&exp, p));
+    }
+
+    // factor out powers of 2: p-1 = 2^s * q
+    let p_minus_1 = p - BigUint::one();
+    let mut q = p_minus_1.clone();
+    let mut s = 0u32;
+    while (&q & BigUint::one()).is_zero() {
+        q >>= 1usize;
+        s += 1;
+    }
+
+    // any quadratic non-residue works as z
+    let mut z = BigUint::from(2u32);
+    while is_quadratic_residue(&z, p) {
+        z += BigUint::one();
+    }
+
+    let mut m = s;
+    let mut c = z.modpow(&q, p);
+    let mut t = n.modpow(&q, p);
+    let mut r = n.modpow(&((&q + BigUint::one()) >> 1usize), p);
+
+    while !t.is_one() {
+        // find the least i with t^(2^i) == 1
+        let mut i = 0u32;
+        let mut t_pow = t.clone();
+        while !t_pow.is_one() {
+            t_pow = (&t_pow * &t_pow) % p;
+            i += 1;
+        }
+
+        let b = c.modpow(&(BigUint::one() << (m - i - 1) as usize), p);
+        m = i;
+        c = (&b * &b) % p;
+        t = (t * &c) % p;
+        r = (r * b) % p;
+    }
+    Some(r)
+}
+
+// affine point, None is the point at infinity
+pub type Point = Option<(BigUint, BigUint)>;
+
+fn mod_inv(a: &BigUint, p: &BigUint) -> BigUint {
+    a.modpow(&(p - BigUint::from(2u32)), p)
+}
+
+pub fn point_add(p1: &Point, p2: &Point, p: &BigUint) -> Point {
+    let ((x1, y1), (x2, y2)) = match (p1, p2) {
+        (None, _) => return p2.clone(),
+        (_, None) => return p1.clone(),
+        (Some(a), Some(b)) => (a, b),
+    };
+
+    let slope = if x1 == x2 {
+        if y1 != y2 || y1.is_zero() {
+            return None;
+        }
+        // doubling: (3x^2 + a) / 2y
+        let num = (BigUint::from(3u32) * x1 * x1 + BigUint::from(CURVE_A)) % p;
+        num * mod_inv(&((y1 << 1usize) % p), p) % p
+    } else {
+        // addition: (y2 - y1) / (x2 - x1)
+        let num = (y2 + p - y1) % p;
+        num * mod_inv(&((x2 + p - x1) % p), p) % p
+    };
+
+    let x3 = (&slope * &slope + (p << 1usize) - x1 - x2) % p;
+    let y3 = (slope * ((x1 + p - &x3) % p) + p - y1) % p;
+    Some((x3, y3))
+}
+
+// double-and-add
+pub fn scalar_mul(k: &BigUint, point: &Point, p: &BigUint) -> Point {
+    let mut result = None;
+    for i in (0..k.bits()).rev() {
+        result = point_add(&result, &result, p);
+        if k.bit(i) {
+            result = point_add(&result, point, p);
+        }
+    }
+    result
+}
@@ -0,0 +1,194 @@
+use num_bigint::BigUint;
+use num_traits::Zero;
+
+// enough for every ECC prime in ECC_V_RSA (up to 208 bits)
+const LIMBS: usize = 4;
+
+// field element in Montgomery form, R = 2^256
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Fe([u64; LIMBS]);
+
+impl Fe {
+    // cheap hash key, Montgomery form is fine since it is a bijection
+    pub fn low_limb(self) -> u64 {
+        self.0[0]
+    }
+}
+
+// arithmetic context for GF(p), p odd and < 2^255
+pub struct Field {
+    p: [u64; LIMBS],
+    p_inv: u64, // -p^-1 mod 2^64
+    r2: Fe,     // R^2 mod p
+    one: Fe,    // R mod p
+    modulus: BigUint,
+}
+
+#[inline(always)]
+fn mac(acc: u64, a: u64, b: u64, carry: u64) -> (u64, u64) {
+    let t = acc as u128 + (a as u128) * (b as u128) + carry as u128;
+    (t as u64, (t >> 64) as u64)
+}
+
+fn to_limbs(v: &BigUint) -> [u64; LIMBS] {
+    let digits = v.to_u64_digits();
+    assert!(digits.len() <= LIMBS, "Field element too large: {:x}", v);
+    let mut limbs = [0u64; LIMBS];
+    limbs[..digits.len()].copy_from_slice(&digits);
+    limbs
+}
+
+impl Field {
+    pub fn new(p: &BigUint) -> Self {
+        assert!(p.bits() < 255 && p.bit(0), "Field modulus must be odd and < 2^255");
+        let limbs = to_limbs(p);
+
+        // Newton iteration for p^-1 mod 2^64
+        let mut inv = 1u64;
+        for _ in 0..6 {
+            inv = inv.wrapping_mul(2u64.wrapping_sub(limbs[0].wrapping_mul(inv)));
+        }
+
+        let r = (BigUint::from(1u32) << (64 * LIMBS)) % p;
+        let r2 = (&r * &r) % p;
+        Field {
+            p: limbs,
+            p_inv: inv.wrapping_neg(),
+            r2: Fe(to_limbs(&r2)),
+            one: Fe(to_limbs(&r)),
+            modulus: p.clone(),
+        }
+    }
+
+    pub fn modulus(&self) -> &BigUint {
+        &self.modulus
+    }
+
+    pub fn zero(&self) -> Fe {
+        Fe([0; LIMBS])
+    }
+
+    pub fn one(&self) -> Fe {
+        self.one
+    }
+
+    pub fn is_zero(&self, a: Fe) -> bool {
+        a.0 == [0; LIMBS]
+    }
+
+    pub fn elem(&self, v: &BigUint) -> Fe {
+        let reduced = Fe(to_limbs(&(v % &self.modulus)));
+        self.mul(reduced, self.r2)
+    }
+
+    pub fn elem_u64(&self, v: u64) -> Fe {
+        self.elem(&BigUint::from(v))
+    }
+
+    // subtract p if a >= p
+    #[inline(always)]
+    fn reduce_once(&self, a: [u64; LIMBS], carry: bool) -> Fe {
+        let mut out = [0u64; LIMBS];
+        let mut borrow = false;
+        for ((o, &x), &m) in out.iter_mut().zip(&a).zip(&self.p) {
+            let (d1, b1) = x.overflowing_sub(m);
+            let (d2, b2) = d1.overflowing_sub(borrow as u64);
+            *o = d2;
+            borrow = b1 || b2;
+        }
+        if carry || !borrow { Fe(out) } else { Fe(a) }
+    }
+
+    pub fn add(&self, a: Fe, b: Fe) -> Fe {
+        let mut out = [0u64; LIMBS];
+        let mut carry = false;
+        for ((o, &x), &y) in out.iter_mut().zip(&a.0).zip(&b.0) {
+            let (s1, c1) = x.overflowing_add(y);
+            let (s2, c2) = s1.overflowing_add(carry as u64);
+            *o = s2;
+            carry = c1 || c2;
+        }
+        self.reduce_once(out, carry)
+    }
+
+    pub fn sub(&self, a: Fe, b: Fe) -> Fe {
+        let mut out = [0u64; LIMBS];
+        let mut borrow = false;
+        for ((o, &x), &y) in out.iter_mut().zip(&a.0).zip(&b.0) {
+            let (d1, b1) = x.overflowing_sub(y);
+            let (d2, b2) = d1.overflowing_sub(borrow as u64);
+            *o = d2;
+            borrow = b1 || b2;
+        }
+        if borrow {
+            // wrapped below zero, add p back
+            let mut carry = false;
+            for (o, &m) in out.iter_mut().zip(&self.p) {
+                let (s1, c1) = o.overflowing_add(m);
+                let (s2, c2) = s1.overflowing_add(carry as u64);
+                *o = s2;
+                carry = c1 || c2;
+            }
+        }
+        Fe(out)
+    }
+
+    pub fn neg(&self, a: Fe) -> Fe {
+        self.sub(self.zero(), a)
+    }
+
+    // CIOS Montgomery multiplication, a * b * R^-1 mod p
+    #[allow(clippy::needless_range_loop)]
+    pub fn mul(&self, a: Fe, b: Fe) -> Fe {
+        let mut t = [0u64; LIMBS + 2];
+        for i in 0..LIMBS {
+            let mut carry = 0u64;
+            for j in 0..LIMBS {
+                let (lo, hi) = mac(t[j], a.0[j], b.0[i], carry);
+                t[j] = lo;
+                carry = hi;
+            }
+            let (s, c) = t[LIMBS].overflowing_add(carry);
+            t[LIMBS] = s;
+            t[LIMBS + 1] = c as u64;
+
+            let m = t[0].wrapping_mul(self.p_inv);
+            let (_, mut carry) = mac(t[0], m, self.p[0], 0);
+            for j in 1..LIMBS {
+                let (lo, hi) = mac(t[j], m, self.p[j], carry);
+                t[j - 1] = lo;
+                carry = hi;
+            }
+            let (s, c) = t[LIMBS].overflowing_add(carry);
+            t[LIMBS - 1] = s;
+            t[LIMBS] = t[LIMBS + 1] + c as u64;
+        }
+        let mut out = [0u64; LIMBS];
+        out.copy_from_slice(&t[..LIMBS]);
+        self.reduce_once(out, t[LIMBS] != 0)
+    }
+
+    pub fn square(&self, a: Fe) -> Fe {
+        self.mul(a, a)
+    }
+
+    pub fn pow(&self, base: Fe, exp: &BigUint) -> Fe {
+        let mut result = self.one;
+        for i in (0..exp.bits()).rev() {
+            result = self.square(result);
+            if exp.bit(i) {
+                result = self.mul(result, base);
+            }
+        }
+        result
+    }
+
+    // Fermat inversion, returns zero for zero
+    pub fn inv(&self, a: Fe) -> Fe {
+        let exp = &self.modulus - BigUint::from(2u32);
+        if exp.is_zero() {
+            return a;
+        }
+        self.pow(a, &exp)
+    }
+}
@@ -0,0 +1,107 @@
+use clap::ValueEnum;
+use num_bigint::BigUint;
+use num_integer::Integer;
+use num_traits::{One, Zero};
+
+use crate::cli::Args;
+use crate::rng::ParamRng;
+use crate::{bit_bounds, next_prime};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum RsaPrimes {
+    /// Next prime after a random start, p and q only have to differ (the original generator)
+    Random,
+    /// FIPS 186-5 A.1.3 random probable primes: top two bits, |p - q| bound, gcd(e, p - 1) = 1
+    Fips,
+    /// FIPS 186-5 A.1.5 probable primes with conditions, p - 1 and p + 1 get large prime factors
+    FipsConditions,
+}
+
+// sqrt(2) * 2^(bits - 1) rounded up, two primes at least this large multiply to exactly 2 * bits bits
+fn lower_bound(bits: u16) -> BigUint {
+    let square = BigUint::one() << (2 * bits as usize - 1);
+    let root = square.sqrt();
+    if &root * &root == square { root } else { root + 1u32 }
+}
+
+// |p - q| > 2^(nlen/2 - 100), below 200-bit moduli the bound is under 1 and only p != q is left
+pub fn far_enough(p: &BigUint, q: &BigUint, bits: u16) -> bool {
+    let distance = if p > q { p - q } else { q - p };
+    match (bits as usize).checked_sub(100) {
+        Some(shift) => distance > BigUint::one() << shift,
+        None => !distance.is_zero(),
+    }
+}
+
+// auxiliary prime length from FIPS 186-5 table A.1, the table starts at 2048-bit moduli so
+// smaller rows scale it down to an eighth of the prime
+fn aux_bits(bits: u16) -> u16 {
+    match 2 * bits as u32 {
+        n if n >= 4096 => 200,
+        n if n >= 3072 => 170,
+        n if n >= 2048 => 140,
+        _ => (bits / 8).max(4),
+    }
+}
+
+fn coprime_to_e(candidate: &BigUint, exponent: &BigUint) -> bool {
+    (candidate - BigUint::one()).gcd(exponent).is_one()
+}
+
+// A.1.3: fresh random odd X >= sqrt(2) 2^(bits - 1) until gcd(X - 1, e) = 1 and X is prime
+pub fn probable_prime(args: &Args, rng: &mut ParamRng, bits: u16) -> BigUint {
+    let (_, max) = bit_bounds(bits);
+    let min = lower_bound(bits);
+    let exponent = BigUint::from(args.exponent);
+    let primality = args.primality();
+
+    for _ in 0..args.search_limit {
+        let candidate = rng.range(&min, &max) | BigUint::one();
+        if candidate <= max && coprime_to_e(&candidate, &exponent) && primality.is_prime(&candidate) {
+            return candidate;
+        }
+    }
+    panic!("Could not find FIPS probable prime for {} bits", bits);
+}
+
+// A.1.5 / B.9: auxiliary primes r1, r2, then a prime p = 1 mod 2 r1 and p = -1 mod r2, so
+// r1 divides p - 1 and r2 divides p + 1
+pub fn conditional_prime(args: &Args, rng: &mut ParamRng, bits: u16) -> BigUint {
+    let (_, max) = bit_bounds(bits);
+    let min = lower_bound(bits);
+    let exponent = BigUint::from(args.exponent);
+    let primality = args.primality();
+
+    let aux = aux_bits(bits);
+    let (aux_min, aux_max) = bit_bounds(aux);
+    let r1 = next_prime(&rng.range(&aux_min, &aux_max), aux, args);
+    let r2 = loop {
+        let r2 = next_prime(&rng.range(&aux_min, &aux_max), aux, args);
+        if r2 != r1 {
+            break r2;
+        }
+    };
+
+    // R = 1 mod 2 r1 and R = -1 mod r2 by CRT, both moduli coprime since r1 != r2 are odd
+    let two_r1 = &r1 << 1usize;
+    let step = &two_r1 * &r2;
+    let r2_inv = r2.modinv(&two_r1).expect("r2 invertible mod 2 r1");
+    let two_r1_inv = two_r1.modinv(&r2).expect("2 r1 invertible mod r2");
+    let residue = (&r2_inv * &r2 + &step - (&two_r1_inv * &two_r1) % &step) % &step;
+
+    let mut remaining = args.search_limit;
+    while remaining > 0 {
+        // first Y >= X in the residue class, then walk it in steps of 2 r1 r2 up to 2^bits
+        let x = rng.range(&min, &max);
+        let mut candidate = &x + (&residue + &step - &x % &step) % &step;
+        while candidate <= max && remaining > 0 {
+            if coprime_to_e(&candidate, &exponent) && primality.is_prime(&candidate) {
+                debug_assert!(((&candidate - 1u32) % &r1).is_zero() && ((&candidate + 1u32) % &r2).is_zero());
+                return candidate;
+            }
+            candidate += &step;
+            remaining -= 1;
+        }
+    }
+    panic!("Could not find FIPS conditional prime for {} bits", bits);
+}
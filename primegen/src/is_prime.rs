@@ -1,15 +1,98 @@
+use clap::ValueEnum;
 use num_bigint::BigUint;
 use num_traits::{One, Zero};
+use std::fmt;
 use std::sync::LazyLock;
 
+use crate::rng::ParamRng;
 use crate::small_primes::SMALL_PRIMES_U16;
 
 pub static SMALL_PRIMES_BIG: LazyLock<Vec<BigUint>> = LazyLock::new(|| {
     SMALL_PRIMES_U16.iter().map(|&p| BigUint::from(p)).collect()
 });
 
-// deterministic Miller-Rabin, 12 bases covers everything we need
-fn miller_rabin(n: &BigUint) -> bool {
+const FIXED_BASES: [u64; 12] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum PrimalityTest {
+    /// Miller-Rabin with the 12 fixed bases 2..37, only proven deterministic below ~3.3e24
+    MillerRabin,
+    /// Baillie-PSW: Miller-Rabin base 2 plus a strong Lucas test, no known counterexample
+    Bpsw,
+}
+
+// which test every primality check in primegen runs
+#[derive(Clone, Copy, Debug)]
+pub struct Primality {
+    pub test: PrimalityTest,
+    // additional Miller-Rabin rounds with random bases on top of `test`
+    pub extra_rounds: u32,
+    // the random bases are derived from (seed, candidate), so results do not depend on call order
+    pub seed: u32,
+}
+
+impl fmt::Display for Primality {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let test = match self.test {
+            PrimalityTest::MillerRabin => "Miller-Rabin (fixed bases 2..37)",
+            PrimalityTest::Bpsw => "Baillie-PSW",
+        };
+        write!(f, "{} + {} random-base Miller-Rabin rounds", test, self.extra_rounds)
+    }
+}
+
+impl Primality {
+    pub fn is_prime(&self, n: &BigUint) -> bool {
+        let two = BigUint::from(2u32);
+        if *n < two {
+            return false;
+        }
+        if *n == two || *n == BigUint::from(3u32) {
+            return true;
+        }
+        if (n & &BigUint::one()).is_zero() {
+            return false;
+        }
+
+        for p in SMALL_PRIMES_BIG.iter() {
+            if (n % p).is_zero() {
+                return n == p;
+            }
+        }
+        self.is_probable_prime(n)
+    }
+
+    // for candidates a sieve already cleared of every small factor, skips the trial division
+    pub fn is_prime_sieved(&self, n: &BigUint) -> bool {
+        let largest_small = SMALL_PRIMES_U16[SMALL_PRIMES_U16.len() - 1];
+        if *n <= BigUint::from(largest_small) {
+            return self.is_prime(n);
+        }
+        self.is_probable_prime(n)
+    }
+
+    fn is_probable_prime(&self, n: &BigUint) -> bool {
+        let two = BigUint::from(2u32);
+        let probable = match self.test {
+            PrimalityTest::MillerRabin => FIXED_BASES.iter().all(|&a| miller_rabin(n, &BigUint::from(a))),
+            PrimalityTest::Bpsw => miller_rabin(n, &two) && strong_lucas(n),
+        };
+        probable && self.random_rounds(n)
+    }
+
+    fn random_rounds(&self, n: &BigUint) -> bool {
+        if self.extra_rounds == 0 {
+            return true;
+        }
+        let bits = u16::try_from(n.bits()).unwrap_or(u16::MAX);
+        let mut rng = ParamRng::new(self.seed, "miller-rabin", bits, 0, &n.to_str_radix(16));
+        let max = n - BigUint::from(2u32);
+        (0..self.extra_rounds).all(|_| miller_rabin(n, &rng.range(&BigUint::from(2u32), &max)))
+    }
+}
+
+// strong probable-prime test to base a, n odd and > a
+fn miller_rabin(n: &BigUint, a: &BigUint) -> bool {
     let one = BigUint::one();
     let two = BigUint::from(2u32);
     let n_minus_1 = n - &one;
@@ -22,47 +105,115 @@ fn miller_rabin(n: &BigUint) -> bool {
         r += 1;
     }
 
-    let bases: [u64; 12] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+    if a >= n {
+        return true;
+    }
 
-    'outer: for &a in &bases {
-        let a_big = BigUint::from(a);
-        if a_big >= *n {
-            continue;
-        }
+    let mut x = a.modpow(&d, n);
+    if x == one || x == n_minus_1 {
+        return true;
+    }
 
-        let mut x = a_big.modpow(&d, n);
-        if x == one || x == n_minus_1 {
-            continue;
+    for _ in 0..r.saturating_sub(1) {
+        x = x.modpow(&two, n);
+        if x == n_minus_1 {
+            return true;
         }
+    }
+    false
+}
 
-        for _ in 0..r.saturating_sub(1) {
-            x = x.modpow(&two, n);
-            if x == n_minus_1 {
-                continue 'outer;
+// Jacobi symbol (a/n) for odd n
+fn jacobi(a: &BigUint, n: &BigUint) -> i32 {
+    let mut a = a % n;
+    let mut n = n.clone();
+    let mut result = 1;
+    while !a.is_zero() {
+        while !a.bit(0) {
+            a >>= 1usize;
+            let r = (&n % 8u32).to_u32_digits().first().copied().unwrap_or(0);
+            if r == 3 || r == 5 {
+                result = -result;
             }
         }
-        return false;
+        std::mem::swap(&mut a, &mut n);
+        if a.bit(0) && a.bit(1) && n.bit(0) && n.bit(1) {
+            result = -result;
+        }
+        a %= &n;
     }
-    true
+    if n.is_one() { result } else { 0 }
 }
 
-pub fn is_prime(n: &BigUint) -> bool {
-    let two = BigUint::from(2u32);
-    if *n < two {
+// x / 2 mod n for odd n
+fn half_mod(x: BigUint, n: &BigUint) -> BigUint {
+    if x.bit(0) { (x + n) >> 1usize } else { x >> 1usize }
+}
+
+// signed small integer mod n
+fn signed_mod(v: i64, n: &BigUint) -> BigUint {
+    let abs = BigUint::from(v.unsigned_abs()) % n;
+    if v >= 0 || abs.is_zero() { abs } else { n - abs }
+}
+
+// strong Lucas probable-prime test with Selfridge's parameters (method A), n odd and > 37
+fn strong_lucas(n: &BigUint) -> bool {
+    // no D with (D/n) = -1 exists for squares
+    let root = n.sqrt();
+    if &root * &root == *n {
         return false;
     }
-    if *n == two || *n == BigUint::from(3u32) {
-        return true;
+
+    // first D in 5, -7, 9, -11, ... with (D/n) = -1
+    let mut d: i64 = 5;
+    loop {
+        match jacobi(&signed_mod(d, n), n) {
+            -1 => break,
+            0 if BigUint::from(d.unsigned_abs()) != *n => return false,
+            _ => d = if d > 0 { -(d + 2) } else { -d + 2 },
+        }
     }
-    if (n & &BigUint::one()).is_zero() {
-        return false;
+    let p = BigUint::one();
+    let q = signed_mod((1 - d) / 4, n);
+    let d = signed_mod(d, n);
+
+    // n + 1 = 2^s * k, k odd
+    let mut k: BigUint = n + 1u32;
+    let mut s = 0u32;
+    while !k.bit(0) {
+        k >>= 1usize;
+        s += 1;
     }
 
-    for p in SMALL_PRIMES_BIG.iter() {
-        if (n % p).is_zero() {
-            return n == p;
+    // left-to-right ladder for U_k, V_k and Q^k starting at k = 1
+    let mut u = BigUint::one();
+    let mut v = p.clone();
+    let mut qk = q.clone();
+    for i in (0..k.bits() - 1).rev() {
+        // doubling
+        u = (&u * &v) % n;
+        v = (&v * &v + n * 2u32 - (&qk << 1usize) % n) % n;
+        qk = (&qk * &qk) % n;
+        if k.bit(i) {
+            // increment, P = 1
+            let next_u = half_mod(&p * &u + &v, n);
+            let next_v = half_mod(&d * &u + &p * &v, n);
+            u = next_u % n;
+            v = next_v % n;
+            qk = (&qk * &q) % n;
         }
     }
 
-    miller_rabin(n)
-}
\ No newline at end of file
+    if u.is_zero() || v.is_zero() {
+        return true;
+    }
+    // V_{2^r k} for 0 < r < s
+    for _ in 1..s {
+        v = (&v * &v + n * 2u32 - (&qk << 1usize) % n) % n;
+        if v.is_zero() {
+            return true;
+        }
+        qk = (&qk * &qk) % n;
+    }
+    false
+}
@@ -1,37 +1,39 @@
+use clap::Parser;
 use num_bigint::BigUint;
 use num_integer::Integer;
 use num_traits::{One, Zero};
 use rayon::prelude::*;
 use std::fs::File;
 use std::io::{BufWriter, Write};
+use std::time::{Duration, Instant};
 
+mod blob;
+mod cli;
+mod comb;
 mod conversions;
+mod curve;
+mod field;
+mod fips;
+mod poly;
+mod point_count;
+mod rng;
+mod sieve;
 mod small_primes;
+mod verify;
 mod is_prime;
 
-use crate::conversions::ECC_V_RSA;
-use crate::is_prime::{is_prime, SMALL_PRIMES_BIG};
-
-const TRIALS: u8 = 20;
-const SEED: u32 = 873267326;
-const PRIME_SEARCH_LIMIT: u32 = 5_000_000;
-const LCG_A: u64 = 6364136223846793005;
-const LCG_C: u64 = 1442695040888963407;
-const RSA_PUBLIC_EXPONENT: u32 = 65537;
-const HEX_CHARS_U256: usize = 64;
-const HEX_CHARS_U1024: usize = 256;
-const HEX_CHARS_U2048: usize = 512;
-
-// simple LCG-based PRNG, deterministic (no OS randomness)
-fn simple_rand(seed: &mut BigUint, max: &BigUint) -> BigUint {
-    if max.is_zero() || *max == BigUint::one() {
-        return BigUint::zero();
-    }
-    let a = BigUint::from(LCG_A);
-    let c = BigUint::from(LCG_C);
-    *seed = (seed.clone() * &a + &c) % max;
-    seed.clone()
-}
+use crate::cli::{Args, Cli, Command};
+use crate::curve::{curve_rhs, is_on_curve, scalar_mul, sqrt_mod, CURVE_A, CURVE_B};
+use crate::fips::RsaPrimes;
+use crate::point_count::prime_order_subgroup;
+use crate::is_prime::SMALL_PRIMES_BIG;
+use crate::rng::ParamRng;
+use crate::sieve::{Form, PrimeSearch};
+
+// crypto-bigint's portable integer types up to U2048, every row uses the smallest that fits
+const UINT_WIDTHS: [u16; 20] = [
+    64, 128, 192, 256, 320, 384, 448, 512, 576, 640, 704, 768, 832, 896, 960, 1024, 1280, 1536, 1792, 2048,
+];
 
 fn bit_bounds(bits: u16) -> (BigUint, BigUint) {
     let min = BigUint::one() << (bits as usize - 1);
@@ -46,8 +48,9 @@ fn is_composite_by_small_primes(value: &BigUint) -> bool {
 }
 
 // find the next prime >= start that fits in the given bit width
-fn next_prime(start: &BigUint, bits: u16) -> BigUint {
+fn next_prime(start: &BigUint, bits: u16, args: &Args) -> BigUint {
     let (min_val, max_val) = bit_bounds(bits);
+    let primality = args.primality();
     let two = BigUint::from(2u32);
 
     let mut candidate = start.clone() | BigUint::one();
@@ -55,12 +58,19 @@ fn next_prime(start: &BigUint, bits: u16) -> BigUint {
         candidate = &min_val | BigUint::one();
     }
 
-    for _ in 0..PRIME_SEARCH_LIMIT {
+    if args.prime_search.for_bits(bits) == PrimeSearch::Sieve {
+        return sieve::search(&candidate, &min_val, &max_val, Form::Prime, args.search_limit, |c| {
+            primality.is_prime_sieved(c)
+        })
+        .unwrap_or_else(|| panic!("Could not find prime for {} bits", bits));
+    }
+
+    for _ in 0..args.search_limit {
         if candidate > max_val {
             candidate = &min_val | BigUint::one();
         }
 
-        if !is_composite_by_small_primes(&candidate) && is_prime(&candidate) {
+        if !is_composite_by_small_primes(&candidate) && primality.is_prime(&candidate) {
             return candidate;
         }
 
@@ -71,8 +81,9 @@ fn next_prime(start: &BigUint, bits: u16) -> BigUint {
 }
 
 // find the next safe prime p = 2q+1 (both p and q prime)
-fn next_safe_prime(start: &BigUint, bits: u16) -> BigUint {
+fn next_safe_prime(start: &BigUint, bits: u16, args: &Args) -> BigUint {
     let (min_val, max_val) = bit_bounds(bits);
+    let primality = args.primality();
     let q_min = &min_val >> 1usize;
     let q_max = &max_val >> 1usize;
     let two = BigUint::from(2u32);
@@ -82,7 +93,19 @@ fn next_safe_prime(start: &BigUint, bits: u16) -> BigUint {
         q = &q_min | BigUint::one();
     }
 
-    for _ in 0..PRIME_SEARCH_LIMIT {
+    if args.prime_search.for_bits(bits) == PrimeSearch::Sieve {
+        return sieve::search(&q, &q_min, &q_max, Form::SafePrime, args.search_limit, |q| {
+            let p_candidate = (q << 1usize) + BigUint::one();
+            p_candidate >= min_val
+                && p_candidate <= max_val
+                && primality.is_prime_sieved(q)
+                && primality.is_prime_sieved(&p_candidate)
+        })
+        .map(|q| (q << 1usize) + BigUint::one())
+        .unwrap_or_else(|| panic!("Could not find safe prime for {} bits", bits));
+    }
+
+    for _ in 0..args.search_limit {
         if q > q_max {
             q = &q_min | BigUint::one();
         }
@@ -93,10 +116,10 @@ fn next_safe_prime(start: &BigUint, bits: u16) -> BigUint {
             if !is_composite_by_small_primes(&p_candidate)
                 && p_candidate >= min_val
                 && p_candidate <= max_val
+                && primality.is_prime(&q)
+                && primality.is_prime(&p_candidate)
             {
-                if is_prime(&q) && is_prime(&p_candidate) {
-                    return p_candidate;
-                }
+                return p_candidate;
             }
         }
         q += &two;
@@ -104,6 +127,29 @@ fn next_safe_prime(start: &BigUint, bits: u16) -> BigUint {
     panic!("Could not find safe prime for {} bits", bits);
 }
 
+// pick a random x until x^3 + ax + b is a square, then take its root as y
+fn random_curve_point(rng: &mut ParamRng, p: &BigUint, search_limit: u32) -> (BigUint, BigUint) {
+    let p_minus_1 = p - BigUint::one();
+    for _ in 0..search_limit {
+        let x = rng.range(&BigUint::one(), &p_minus_1);
+        // skip y = 0, those points only have order 2
+        if let Some(y) = sqrt_mod(&curve_rhs(&x, p), p).filter(|y| !y.is_zero()) {
+            return (x, y);
+        }
+    }
+    panic!("Could not find a curve point for p = {}", p);
+}
+
+// random point of prime order n, cleared of the cofactor
+fn random_subgroup_point(rng: &mut ParamRng, p: &BigUint, cofactor: u32, search_limit: u32) -> (BigUint, BigUint) {
+    loop {
+        let point = Some(random_curve_point(rng, p, search_limit));
+        if let Some(g) = scalar_mul(&BigUint::from(cofactor), &point, p) {
+            return g;
+        }
+    }
+}
+
 struct TrialResult {
     ecc_bits: u16,
     rsa_bits: u16,
@@ -111,103 +157,130 @@ struct TrialResult {
     exponent: BigUint,
     p: BigUint,
     q: BigUint,
+    // private exponent d = e^-1 mod φ(n) and its CRT form, so the firmware never inverts
+    d: BigUint,
+    dp: BigUint,
+    dq: BigUint,
+    qinv: BigUint,
     ecc_prime: BigUint,
+    ecc_order: BigUint,
+    ecc_cofactor: u32,
     gen_x: BigUint,
     gen_y: BigUint,
     ecc_private_key1: BigUint,
     ecc_private_key2: BigUint,
+    // --comb-teeth generator comb, empty without it
+    comb: Vec<(BigUint, BigUint)>,
+    // time spent in next_prime / next_safe_prime, reported per row
+    search_time: Duration,
+}
+
+// random prime of the given size with e coprime to p - 1, distinct from `other`
+fn random_rsa_prime(args: &Args, rng: &mut ParamRng, bits: u16, other: Option<&BigUint>) -> BigUint {
+    let (min_val, max_val) = bit_bounds(bits);
+    let exponent = BigUint::from(args.exponent);
+    loop {
+        let prime = match args.rsa_primes {
+            RsaPrimes::Random => next_prime(&rng.range(&min_val, &max_val), bits, args),
+            RsaPrimes::Fips => fips::probable_prime(args, rng, bits),
+            RsaPrimes::FipsConditions => fips::conditional_prime(args, rng, bits),
+        };
+        let distinct = match other {
+            None => true,
+            Some(other) if args.rsa_primes == RsaPrimes::Random => prime != *other,
+            Some(other) => fips::far_enough(&prime, other, bits),
+        };
+        if distinct && (&prime - BigUint::one()).gcd(&exponent).is_one() {
+            return prime;
+        }
+    }
 }
 
-fn generate_trials(ecc_bits: u16, rsa_bits: u16) -> Vec<TrialResult> {
+// every parameter draws from its own stream, so trials are independent of each other
+fn generate_trial(args: &Args, ecc_bits: u16, rsa_bits: u16, trial: u8) -> TrialResult {
     let rsa_prime_bits = rsa_bits / 2;
-    let seed_base = BigUint::from(SEED);
-    let mut seed2 = &seed_base + BigUint::from(ecc_bits as u64);
-
-    // Deterministic starting candidate for RSA primes
-    let (rsa_min, rsa_max) = bit_bounds(rsa_prime_bits);
-    let rsa_range = &rsa_max - &rsa_min + BigUint::one();
-    let seed_mult = &seed_base * BigUint::from(rsa_bits as u64) * BigUint::from(LCG_A);
-    let seed_add = &seed_mult + BigUint::from(LCG_C);
-    let mut rsa_candidate = &rsa_min + (&seed_add % &rsa_range);
-    rsa_candidate = &rsa_candidate | &rsa_min;
-
-    // Deterministic starting candidate for ECC primes
+    let rsa_rng = |field| ParamRng::new(args.seed, "rsa", rsa_bits, trial, field);
+    let ecc_rng = |field| ParamRng::new(args.seed, "ecc", ecc_bits, trial, field);
+
+    // RSA: two distinct primes of rsa_prime_bits each, drawn as --rsa-primes says
+    let search_start = Instant::now();
+    let p1 = random_rsa_prime(args, &mut rsa_rng("p"), rsa_prime_bits, None);
+    let p2 = random_rsa_prime(args, &mut rsa_rng("q"), rsa_prime_bits, Some(&p1));
+    let mut search_time = search_start.elapsed();
+
+    // ECC: one safe prime of ecc_bits whose curve has a large prime-order subgroup
     let (ecc_min, ecc_max) = bit_bounds(ecc_bits);
-    let ecc_range = &ecc_max - &ecc_min + BigUint::one();
-    let seed_mult_ecc = &seed_base * BigUint::from(ecc_bits as u64) * BigUint::from(LCG_A);
-    let seed_add_ecc = &seed_mult_ecc + BigUint::from(LCG_C);
-    let mut ecc_candidate = &ecc_min + (&seed_add_ecc % &ecc_range);
-    ecc_candidate = &ecc_candidate | &ecc_min;
-
-    let mut results = Vec::with_capacity(TRIALS as usize);
-
-    for _ in 0..TRIALS {
-        // RSA: two regular primes of rsa_prime_bits each
-        let p1 = next_prime(&rsa_candidate, rsa_prime_bits);
-        rsa_candidate = &p1 + BigUint::from(4u32);
-        let p2 = next_prime(&rsa_candidate, rsa_prime_bits);
-        rsa_candidate = &p2 + BigUint::from(4u32);
-
-        // ECC: one safe prime of ecc_bits
-        let p3 = next_safe_prime(&ecc_candidate, ecc_bits);
-        ecc_candidate = &p3 + BigUint::from(4u32);
-
-        // RSA: make sure e is coprime to totient
-        let modulus = &p1 * &p2;
-        let totient = (&p1 - BigUint::one()) * (&p2 - BigUint::one());
-        let exponent = BigUint::from(RSA_PUBLIC_EXPONENT);
-        assert!(
-            totient.gcd(&exponent) == BigUint::one(),
-            "e={} not coprime to totient for {}-bit RSA primes",
-            RSA_PUBLIC_EXPONENT,
-            rsa_prime_bits
-        );
+    let mut prime_rng = ecc_rng("prime");
+    let (p3, subgroup) = loop {
+        let search_start = Instant::now();
+        let p3 = next_safe_prime(&prime_rng.range(&ecc_min, &ecc_max), ecc_bits, args);
+        search_time += search_start.elapsed();
+        // the firmware runs the row on ecc_bits-wide integers, so the order has to fit as well
+        if let Some(subgroup) = prime_order_subgroup(&p3, &args.primality())
+            .filter(|subgroup| subgroup.subgroup_order.bits() <= ecc_bits as u64)
+        {
+            debug_assert!(subgroup.order == &subgroup.subgroup_order * subgroup.cofactor);
+            break (p3, subgroup);
+        }
+    };
 
-        let max_session = if modulus > BigUint::from(3u32) {
-            &modulus - BigUint::from(3u32)
-        } else {
-            BigUint::from(100u32)
-        };
-        let session_key = simple_rand(&mut seed2, &max_session) + BigUint::from(2u32);
-
-        // ECC parameters
-        let p3_minus_1 = &p3 - BigUint::one();
-        let gen_x = if p3 > BigUint::one() {
-            simple_rand(&mut seed2, &p3_minus_1) + BigUint::one()
-        } else {
-            BigUint::from(2u32)
-        };
-        let gen_y = if p3 > BigUint::one() {
-            simple_rand(&mut seed2, &p3_minus_1) + BigUint::one()
-        } else {
-            BigUint::from(2u32)
-        };
-        let ecc_private_key1 = if p3 > BigUint::one() {
-            simple_rand(&mut seed2, &p3_minus_1) + BigUint::one()
-        } else {
-            BigUint::from(2u32)
-        };
-        let ecc_private_key2 = if p3 > BigUint::one() {
-            simple_rand(&mut seed2, &p3_minus_1) + BigUint::one()
-        } else {
-            BigUint::from(3u32)
-        };
+    // RSA: make sure e is coprime to totient
+    let modulus = &p1 * &p2;
+    if args.rsa_primes != RsaPrimes::Random {
+        assert_eq!(modulus.bits(), rsa_bits as u64, "FIPS modulus is not {} bits", rsa_bits);
+    }
+    let totient = (&p1 - BigUint::one()) * (&p2 - BigUint::one());
+    let exponent = BigUint::from(args.exponent);
+    assert!(
+        totient.gcd(&exponent) == BigUint::one(),
+        "e={} not coprime to totient for {}-bit RSA primes",
+        args.exponent,
+        rsa_prime_bits
+    );
 
-        results.push(TrialResult {
-            ecc_bits,
-            rsa_bits,
-            session_key,
-            exponent,
-            p: p1,
-            q: p2,
-            ecc_prime: p3,
-            gen_x,
-            gen_y,
-            ecc_private_key1,
-            ecc_private_key2,
-        });
+    let d = exponent.modinv(&totient).expect("e invertible mod totient");
+    let dp = &d % (&p1 - BigUint::one());
+    let dq = &d % (&p2 - BigUint::one());
+    let qinv = p2.modinv(&p1).expect("q invertible mod p");
+
+    let session_key = rsa_rng("session_key").range(&BigUint::from(2u32), &(&modulus - BigUint::from(2u32)));
+
+    // ECC parameters, private keys are scalars mod the subgroup order
+    let n_minus_1 = &subgroup.subgroup_order - BigUint::one();
+    let (gen_x, gen_y) = random_subgroup_point(&mut ecc_rng("generator"), &p3, subgroup.cofactor, args.search_limit);
+    let ecc_private_key1 = ecc_rng("private_key1").range(&BigUint::one(), &n_minus_1);
+    let ecc_private_key2 = ecc_rng("private_key2").range(&BigUint::one(), &n_minus_1);
+    let comb = comb::comb_table(&Some((gen_x.clone(), gen_y.clone())), &subgroup.subgroup_order, args.comb_teeth, &p3)
+        .unwrap_or_else(|| panic!("Generator comb reaches the point at infinity for p = {:x}", p3));
+
+    TrialResult {
+        ecc_bits,
+        rsa_bits,
+        session_key,
+        exponent,
+        p: p1,
+        q: p2,
+        d,
+        dp,
+        dq,
+        qinv,
+        ecc_prime: p3,
+        ecc_order: subgroup.subgroup_order,
+        ecc_cofactor: subgroup.cofactor,
+        gen_x,
+        gen_y,
+        ecc_private_key1,
+        ecc_private_key2,
+        comb,
+        search_time,
     }
-    results
+}
+
+fn generate_trials(args: &Args, ecc_bits: u16, rsa_bits: u16) -> Vec<TrialResult> {
+    (0..args.trials)
+        .into_par_iter()
+        .map(|trial| generate_trial(args, ecc_bits, rsa_bits, trial))
+        .collect()
 }
 
 fn biguint_to_be_hex(v: &BigUint, num_hex_chars: usize) -> String {
@@ -222,28 +295,69 @@ fn biguint_to_be_hex(v: &BigUint, num_hex_chars: usize) -> String {
     format!("{:0>width$}", hex, width = num_hex_chars)
 }
 
-fn fmt_u256(v: &BigUint) -> String {
-    format!("U256::from_be_hex(\"{}\")", biguint_to_be_hex(v, HEX_CHARS_U256))
+fn uint_width(bits: u16) -> u16 {
+    *UINT_WIDTHS.iter().find(|&&w| w >= bits).unwrap_or_else(|| panic!("No integer type for {} bits", bits))
 }
 
-fn fmt_u1024(v: &BigUint) -> String {
-    format!("U1024::from_be_hex(\"{}\")", biguint_to_be_hex(v, HEX_CHARS_U1024))
+// (RSA modulus, RSA prime, ECC field) widths of a row, mirrored by the dispatch in ee-experiment's main
+fn row_widths(ecc_bits: u16, rsa_bits: u16) -> (u16, u16, u16) {
+    (uint_width(rsa_bits), uint_width(rsa_bits.div_ceil(2)), uint_width(ecc_bits))
 }
 
-fn fmt_u2048(v: &BigUint) -> String {
-    format!("U2048::from_be_hex(\"{}\")", biguint_to_be_hex(v, HEX_CHARS_U2048))
+fn comb_points(teeth: u8) -> usize {
+    (1usize << teeth) - 1
+}
+
+fn fmt_uint(v: &BigUint, width: u16) -> String {
+    format!("U{}::from_be_hex(\"{}\")", width, biguint_to_be_hex(v, width as usize / 4))
 }
 
 fn main() {
+    let cli = Cli::parse();
+    match cli.command {
+        Some(Command::Verify(verify_args)) => {
+            if !verify::verify(&verify_args.path) {
+                std::process::exit(1);
+            }
+        }
+        Some(Command::Generate(args)) => generate(args),
+        None => generate(cli.generate),
+    }
+}
+
+fn generate(args: Args) {
+    let rows = args.rows();
     let _ = &*SMALL_PRIMES_BIG; // force init
 
-    let all_results: Vec<Vec<TrialResult>> = ECC_V_RSA
+    let all_results: Vec<Vec<TrialResult>> = rows
         .par_iter()
         .map(|&(ecc_bits, rsa_bits)| {
-            let results = generate_trials(ecc_bits, rsa_bits);
+            let results = generate_trials(&args, ecc_bits, rsa_bits);
+            let search_time = |results: &[TrialResult]| -> f64 {
+                results.iter().map(|r| r.search_time).sum::<Duration>().as_secs_f64() * 1000.0
+            };
+            let mut times = format!("{:.1} ms ({:?})", search_time(&results), args.prime_search);
+            if args.compare {
+                // the same trials again, the table comes out identical so only the time is kept
+                let other = Args { prime_search: args.prime_search.other(), ..args.clone() };
+                let other_time = search_time(&generate_trials(&other, ecc_bits, rsa_bits));
+                let (sieve, trial_division) = match args.prime_search {
+                    PrimeSearch::Sieve => (search_time(&results), other_time),
+                    PrimeSearch::TrialDivision => (other_time, search_time(&results)),
+                };
+                times = format!(
+                    "{:.1} ms (Sieve), {:.1} ms (TrialDivision), sieve speedup {:.2}x",
+                    sieve,
+                    trial_division,
+                    trial_division / sieve
+                );
+            }
             eprintln!(
-                "ECC {} bits / RSA {} bits - {} trials generated",
-                ecc_bits, rsa_bits, results.len()
+                "ECC {} bits / RSA {} bits - {} trials generated, prime search {}",
+                ecc_bits,
+                rsa_bits,
+                results.len(),
+                times
             );
             results
         })
@@ -251,67 +365,188 @@ fn main() {
 
     let entries: Vec<_> = all_results.iter().flat_map(|v| v.iter()).collect();
 
-    let file = File::create("./lookup.rs").expect("Failed to create lookup.rs");
+    for e in &entries {
+        assert!(
+            is_on_curve(&e.gen_x, &e.gen_y, &e.ecc_prime),
+            "Generator ({:x}, {:x}) is not on the curve for p = {:x}",
+            e.gen_x,
+            e.gen_y,
+            e.ecc_prime
+        );
+        assert!(
+            scalar_mul(&e.ecc_order, &Some((e.gen_x.clone(), e.gen_y.clone())), &e.ecc_prime).is_none(),
+            "Generator does not have order {:x} for p = {:x}",
+            e.ecc_order,
+            e.ecc_prime
+        );
+    }
+
+    let file = File::create(&args.output)
+        .unwrap_or_else(|e| panic!("Failed to create {}: {}", args.output.display(), e));
     let mut w = BufWriter::new(file);
 
     writeln!(w, "// This file is auto-generated. Do not edit manually.").unwrap();
-    writeln!(w, "use crypto_bigint::{{U256, U1024, U2048}};\n").unwrap();
-
-    writeln!(w, "// curve: y^2 = x^3 + ax + b (mod p)").unwrap();
-    writeln!(w, "pub const CURVE_A: U256 = {};", fmt_u256(&BigUint::from(2u32))).unwrap();
-    writeln!(w, "pub const CURVE_B: U256 = {};", fmt_u256(&BigUint::from(3u32))).unwrap();
-    writeln!(w, "pub const TRIALS: u8 = {};\n", TRIALS).unwrap();
+    writeln!(w, "// primegen {}", env!("CARGO_PKG_VERSION")).unwrap();
+    writeln!(w, "// invocation: {}", std::env::args().collect::<Vec<_>>().join(" ")).unwrap();
+    writeln!(
+        w,
+        "// trials: {}, seed: {}, search limit: {}, exponent: {}",
+        args.trials, args.seed, args.search_limit, args.exponent
+    )
+    .unwrap();
+    writeln!(w, "// primality: {}", args.primality()).unwrap();
+    writeln!(w, "// rsa primes: {:?}", args.rsa_primes).unwrap();
+    writeln!(w, "// generator comb teeth: {}", args.comb_teeth).unwrap();
+    let row_list: Vec<String> = rows.iter().map(|(ecc, rsa)| format!("{}/{}", ecc, rsa)).collect();
+    writeln!(w, "// rows (ECC/RSA bits): {}", row_list.join(", ")).unwrap();
+    let mut widths: Vec<u16> = rows.iter().flat_map(|&(ecc, rsa)| <[u16; 3]>::from(row_widths(ecc, rsa))).collect();
+    widths.sort_unstable();
+    widths.dedup();
+    let aliases: Vec<String> = widths.iter().map(|w| format!("U{}", w)).collect();
+    writeln!(w, "use crypto_bigint::Uint;").unwrap();
+    writeln!(w, "#[cfg(not(feature = \"param-blob\"))]").unwrap();
+    writeln!(w, "use crypto_bigint::{{{}}};\n", aliases.join(", ")).unwrap();
+
+    writeln!(w, "// curve: y^2 = x^3 + {}x + {} (mod p)", CURVE_A, CURVE_B).unwrap();
+    writeln!(w, "pub const TRIALS: u8 = {};", args.trials).unwrap();
+    writeln!(w, "pub const COMB_TEETH: u8 = {};\n", args.comb_teeth).unwrap();
 
     // Conversion table
-    writeln!(w, "pub const ECC_V_RSA: [(u16, u16); {}] = [", ECC_V_RSA.len()).unwrap();
-    for &(ecc, rsa) in &ECC_V_RSA {
+    writeln!(w, "pub const ECC_V_RSA: [(u16, u16); {}] = [", rows.len()).unwrap();
+    for &(ecc, rsa) in &rows {
         writeln!(w, "    ({}, {}),", ecc, rsa).unwrap();
     }
     writeln!(w, "];\n").unwrap();
 
-    // Struct definitions
+    // Struct definitions, N limbs for the RSA modulus, P for its primes, E for the ECC field
     writeln!(w, "#[derive(Clone, Copy)]").unwrap();
-    writeln!(w, "pub struct RSA {{ pub session_key: U2048, pub exponent: U256, pub p: U1024, pub q: U1024 }}\n").unwrap();
+    writeln!(w, "pub struct RSA<const N: usize, const P: usize> {{ pub session_key: Uint<N>, pub exponent: Uint<N>, pub p: Uint<P>, pub q: Uint<P>, pub d: Uint<N>, pub dp: Uint<P>, pub dq: Uint<P>, pub qinv: Uint<P> }}\n").unwrap();
     writeln!(w, "#[derive(Clone, Copy)]").unwrap();
-    writeln!(w, "pub struct Point {{ pub x: U256, pub y: U256 }}\n").unwrap();
+    writeln!(w, "pub struct Point<const E: usize> {{ pub x: Uint<E>, pub y: Uint<E> }}\n").unwrap();
     writeln!(w, "#[derive(Clone, Copy)]").unwrap();
-    writeln!(w, "pub struct EccCurve {{ pub a: U256, pub b: U256, pub p: U256, pub generator: Point }}\n").unwrap();
+    writeln!(w, "pub struct EccCurve<const E: usize> {{ pub a: Uint<E>, pub b: Uint<E>, pub p: Uint<E>, pub order: Uint<E>, pub cofactor: u32, pub generator: Point<E> }}\n").unwrap();
     writeln!(w, "#[derive(Clone, Copy)]").unwrap();
-    writeln!(w, "pub struct ECC {{ pub curve: EccCurve, pub private_key1: U256, pub private_key2: U256 }}\n").unwrap();
+    writeln!(w, "pub struct ECC<const E: usize> {{ pub curve: EccCurve<E>, pub private_key1: Uint<E>, pub private_key2: Uint<E> }}\n").unwrap();
     writeln!(w, "#[derive(Clone, Copy)]").unwrap();
-    writeln!(w, "pub struct KeySize {{ pub ecc_bits: u16, pub rsa_bits: u16, pub rsa: RSA, pub ecc: ECC }}\n").unwrap();
-
-    // Lookup array
-    writeln!(w, "pub const LOOKUP_TABLE: [KeySize; {}] = [", entries.len()).unwrap();
-    for (i, e) in entries.iter().enumerate() {
-        let comma = if i < entries.len() - 1 { "," } else { "" };
-        writeln!(w, "    KeySize {{").unwrap();
-        writeln!(w, "        ecc_bits: {},", e.ecc_bits).unwrap();
-        writeln!(w, "        rsa_bits: {},", e.rsa_bits).unwrap();
-        writeln!(w, "        rsa: RSA {{").unwrap();
-        writeln!(w, "            session_key: {},", fmt_u2048(&e.session_key)).unwrap();
-        writeln!(w, "            exponent: {},", fmt_u256(&e.exponent)).unwrap();
-        writeln!(w, "            p: {},", fmt_u1024(&e.p)).unwrap();
-        writeln!(w, "            q: {}", fmt_u1024(&e.q)).unwrap();
-        writeln!(w, "        }},").unwrap();
-        writeln!(w, "        ecc: ECC {{").unwrap();
-        writeln!(w, "            curve: EccCurve {{").unwrap();
-        writeln!(w, "                a: CURVE_A, b: CURVE_B,").unwrap();
-        writeln!(w, "                p: {},", fmt_u256(&e.ecc_prime)).unwrap();
-        writeln!(w, "                generator: Point {{ x: {}, y: {} }}", fmt_u256(&e.gen_x), fmt_u256(&e.gen_y)).unwrap();
-        writeln!(w, "            }},").unwrap();
-        writeln!(w, "            private_key1: {},", fmt_u256(&e.ecc_private_key1)).unwrap();
-        writeln!(w, "            private_key2: {}", fmt_u256(&e.ecc_private_key2)).unwrap();
-        writeln!(w, "        }}").unwrap();
-        writeln!(w, "    }}{}", comma).unwrap();
+    writeln!(w, "pub struct KeySize<const N: usize, const P: usize, const E: usize> {{ pub ecc_bits: u16, pub rsa_bits: u16, pub rsa: RSA<N, P>, pub ecc: ECC<E> }}\n").unwrap();
+
+    // One array per row at its own widths, left out when the firmware reads the binary blob instead
+    for (size_idx, (&(ecc_bits, rsa_bits), results)) in rows.iter().zip(&all_results).enumerate() {
+        let (n, p, e_width) = row_widths(ecc_bits, rsa_bits);
+        writeln!(w, "#[cfg(not(feature = \"param-blob\"))]").unwrap();
+        writeln!(
+            w,
+            "pub static ROW_{}: [KeySize<{{ U{}::LIMBS }}, {{ U{}::LIMBS }}, {{ U{}::LIMBS }}>; {}] = [",
+            size_idx,
+            n,
+            p,
+            e_width,
+            results.len()
+        )
+        .unwrap();
+        for (i, e) in results.iter().enumerate() {
+            let comma = if i < results.len() - 1 { "," } else { "" };
+            writeln!(w, "    KeySize {{").unwrap();
+            writeln!(w, "        ecc_bits: {},", e.ecc_bits).unwrap();
+            writeln!(w, "        rsa_bits: {},", e.rsa_bits).unwrap();
+            writeln!(w, "        rsa: RSA {{").unwrap();
+            writeln!(w, "            session_key: {},", fmt_uint(&e.session_key, n)).unwrap();
+            writeln!(w, "            exponent: {},", fmt_uint(&e.exponent, n)).unwrap();
+            writeln!(w, "            p: {},", fmt_uint(&e.p, p)).unwrap();
+            writeln!(w, "            q: {},", fmt_uint(&e.q, p)).unwrap();
+            writeln!(w, "            d: {},", fmt_uint(&e.d, n)).unwrap();
+            writeln!(w, "            dp: {},", fmt_uint(&e.dp, p)).unwrap();
+            writeln!(w, "            dq: {},", fmt_uint(&e.dq, p)).unwrap();
+            writeln!(w, "            qinv: {}", fmt_uint(&e.qinv, p)).unwrap();
+            writeln!(w, "        }},").unwrap();
+            writeln!(w, "        ecc: ECC {{").unwrap();
+            writeln!(w, "            curve: EccCurve {{").unwrap();
+            writeln!(
+                w,
+                "                a: {}, b: {},",
+                fmt_uint(&BigUint::from(CURVE_A), e_width),
+                fmt_uint(&BigUint::from(CURVE_B), e_width)
+            )
+            .unwrap();
+            writeln!(w, "                p: {},", fmt_uint(&e.ecc_prime, e_width)).unwrap();
+            writeln!(w, "                order: {}, cofactor: {},", fmt_uint(&e.ecc_order, e_width), e.ecc_cofactor).unwrap();
+            writeln!(
+                w,
+                "                generator: Point {{ x: {}, y: {} }}",
+                fmt_uint(&e.gen_x, e_width),
+                fmt_uint(&e.gen_y, e_width)
+            )
+            .unwrap();
+            writeln!(w, "            }},").unwrap();
+            writeln!(w, "            private_key1: {},", fmt_uint(&e.ecc_private_key1, e_width)).unwrap();
+            writeln!(w, "            private_key2: {}", fmt_uint(&e.ecc_private_key2, e_width)).unwrap();
+            writeln!(w, "        }}").unwrap();
+            writeln!(w, "    }}{}", comma).unwrap();
+        }
+        writeln!(w, "];\n").unwrap();
+
+        // generator comb per trial, empty arrays without --comb-teeth
+        writeln!(w, "#[cfg(not(feature = \"param-blob\"))]").unwrap();
+        writeln!(
+            w,
+            "pub static COMB_{}: [[Point<{{ U{}::LIMBS }}>; {}]; {}] = [",
+            size_idx,
+            e_width,
+            comb_points(args.comb_teeth),
+            results.len()
+        )
+        .unwrap();
+        for e in results {
+            writeln!(w, "    [").unwrap();
+            for (x, y) in &e.comb {
+                writeln!(w, "        Point {{ x: {}, y: {} }},", fmt_uint(x, e_width), fmt_uint(y, e_width)).unwrap();
+            }
+            writeln!(w, "    ],").unwrap();
+        }
+        writeln!(w, "];\n").unwrap();
     }
-    writeln!(w, "];").unwrap();
+
+    // typed access by row index, None unless N, P and E are that row's widths
+    writeln!(w, "#[cfg(not(feature = \"param-blob\"))]").unwrap();
+    writeln!(
+        w,
+        "pub fn entry<const N: usize, const P: usize, const E: usize>(size_idx: usize, trial: usize) -> Option<KeySize<N, P, E>> {{"
+    )
+    .unwrap();
+    writeln!(w, "    match size_idx {{").unwrap();
+    for size_idx in 0..rows.len() {
+        writeln!(w, "        {} => ROW_{}[trial].cast(),", size_idx, size_idx).unwrap();
+    }
+    writeln!(w, "        _ => None,").unwrap();
+    writeln!(w, "    }}").unwrap();
+    writeln!(w, "}}\n").unwrap();
+
+    writeln!(w, "#[cfg(not(feature = \"param-blob\"))]").unwrap();
+    writeln!(
+        w,
+        "pub fn comb_point<const E: usize>(size_idx: usize, trial: usize, idx: usize) -> Option<Point<E>> {{"
+    )
+    .unwrap();
+    writeln!(w, "    match size_idx {{").unwrap();
+    for size_idx in 0..rows.len() {
+        writeln!(w, "        {} => COMB_{}[trial].get(idx)?.cast(),", size_idx, size_idx).unwrap();
+    }
+    writeln!(w, "        _ => None,").unwrap();
+    writeln!(w, "    }}").unwrap();
+    writeln!(w, "}}").unwrap();
 
     w.flush().unwrap();
+
+    let blob_path = args.output.with_extension("bin");
+    blob::write_blob(&blob_path, &rows, args.trials, args.comb_teeth, &entries)
+        .unwrap_or_else(|e| panic!("Failed to write {}: {}", blob_path.display(), e));
+
     println!(
-        "Generated lookup.rs with {} KeySize entries ({} key-size pairs x {} trials)",
+        "Generated {} and {} with {} KeySize entries ({} key-size pairs x {} trials)",
+        args.output.display(),
+        blob_path.display(),
         entries.len(),
-        ECC_V_RSA.len(),
-        TRIALS
+        rows.len(),
+        args.trials
     );
 }
@@ -0,0 +1,666 @@
+use num_bigint::BigUint;
+use num_integer::Roots;
+use num_traits::{One, ToPrimitive, Zero};
+use std::collections::HashMap;
+
+use crate::curve::{sqrt_mod, CURVE_A, CURVE_B};
+use crate::field::{Fe, Field};
+use crate::is_prime::Primality;
+use crate::poly::{Poly, PolyRing, QuotientRing};
+use crate::small_primes::SMALL_PRIMES_U16;
+
+// largest cofactor h = #E / n accepted for a generated curve
+pub const MAX_COFACTOR: u32 = 8;
+
+// Schoof narrows the trace down until at most 2^BSGS_RANGE_BITS candidates remain,
+// baby-step giant-step does the rest (small curves skip Schoof entirely). The l up to
+// SCHOOF_CHEAP_L cost far less than the BSGS they save, so they keep going down to
+// 2^BSGS_MIN_RANGE_BITS candidates
+const BSGS_RANGE_BITS: u64 = 42;
+const BSGS_MIN_RANGE_BITS: u64 = 24;
+const SCHOOF_CHEAP_L: u64 = 13;
+
+// giant steps are normalised to affine this many at a time
+const BSGS_BATCH: u64 = 4096;
+
+pub struct CurveOrder {
+    pub order: BigUint,          // #E(F_p)
+    pub subgroup_order: BigUint, // n, prime
+    pub cofactor: u32,           // h = #E / n
+}
+
+// affine point, None is the point at infinity
+type Point = Option<(Fe, Fe)>;
+
+// Jacobian point (X / Z^2, Y / Z^3), Z = 0 is the point at infinity
+#[derive(Clone, Copy)]
+struct Jacobian {
+    x: Fe,
+    y: Fe,
+    z: Fe,
+}
+
+// y^2 = x^3 + ax + b over F_p
+struct Curve<'a> {
+    f: &'a Field,
+    a: Fe,
+    b: Fe,
+}
+
+impl<'a> Curve<'a> {
+    fn new(f: &'a Field, a: &BigUint, b: &BigUint) -> Self {
+        Curve { f, a: f.elem(a), b: f.elem(b) }
+    }
+
+    fn add(&self, p1: Point, p2: Point) -> Point {
+        let f = self.f;
+        let ((x1, y1), (x2, y2)) = match (p1, p2) {
+            (None, _) => return p2,
+            (_, None) => return p1,
+            (Some(a), Some(b)) => (a, b),
+        };
+
+        let slope = if x1 == x2 {
+            if y1 != y2 || f.is_zero(y1) {
+                return None;
+            }
+            let x1_sq = f.square(x1);
+            let num = f.add(f.add(f.add(x1_sq, x1_sq), x1_sq), self.a);
+            f.mul(num, f.inv(f.add(y1, y1)))
+        } else {
+            f.mul(f.sub(y2, y1), f.inv(f.sub(x2, x1)))
+        };
+
+        let x3 = f.sub(f.sub(f.square(slope), x1), x2);
+        let y3 = f.sub(f.mul(slope, f.sub(x1, x3)), y1);
+        Some((x3, y3))
+    }
+
+    fn mul(&self, k: &BigUint, p: Point) -> Point {
+        let mut result = None;
+        for i in (0..k.bits()).rev() {
+            result = self.add(result, result);
+            if k.bit(i) {
+                result = self.add(result, p);
+            }
+        }
+        result
+    }
+
+    fn double_jacobian(&self, p: Jacobian) -> Jacobian {
+        let f = self.f;
+        if f.is_zero(p.z) || f.is_zero(p.y) {
+            return Jacobian { x: f.one(), y: f.one(), z: f.zero() };
+        }
+        let xx = f.square(p.x);
+        let yy = f.square(p.y);
+        let zz = f.square(p.z);
+        let s = f.mul(f.elem_u64(4), f.mul(p.x, yy));
+        let m = f.add(f.mul(f.elem_u64(3), xx), f.mul(self.a, f.square(zz)));
+        let x3 = f.sub(f.square(m), f.add(s, s));
+        let y3 = f.sub(f.mul(m, f.sub(s, x3)), f.mul(f.elem_u64(8), f.square(yy)));
+        let z3 = f.mul(f.add(p.y, p.y), p.z);
+        Jacobian { x: x3, y: y3, z: z3 }
+    }
+
+    // Jacobian + affine
+    fn add_mixed(&self, p: Jacobian, (x2, y2): (Fe, Fe)) -> Jacobian {
+        let f = self.f;
+        if f.is_zero(p.z) {
+            return Jacobian { x: x2, y: y2, z: f.one() };
+        }
+        let z1z1 = f.square(p.z);
+        let u2 = f.mul(x2, z1z1);
+        let s2 = f.mul(y2, f.mul(p.z, z1z1));
+        let h = f.sub(u2, p.x);
+        let r = f.sub(s2, p.y);
+        if f.is_zero(h) {
+            return if f.is_zero(r) {
+                self.double_jacobian(p)
+            } else {
+                Jacobian { x: f.one(), y: f.one(), z: f.zero() }
+            };
+        }
+        let hh = f.square(h);
+        let hhh = f.mul(h, hh);
+        let v = f.mul(p.x, hh);
+        let x3 = f.sub(f.sub(f.square(r), hhh), f.add(v, v));
+        let y3 = f.sub(f.mul(r, f.sub(v, x3)), f.mul(p.y, hhh));
+        Jacobian { x: x3, y: y3, z: f.mul(p.z, h) }
+    }
+
+    // start + i * step for 0 <= i < count, normalised with a single inversion
+    fn chain(&self, start: Point, step: Point, count: u64) -> Vec<Point> {
+        let f = self.f;
+        let Some(step) = step else {
+            return vec![start; count as usize];
+        };
+
+        let mut cur = match start {
+            Some((x, y)) => Jacobian { x, y, z: f.one() },
+            None => Jacobian { x: f.one(), y: f.one(), z: f.zero() },
+        };
+        let mut points = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            points.push(cur);
+            cur = self.add_mixed(cur, step);
+        }
+
+        // Montgomery's trick: prefix products of the non-zero Z
+        let mut prefix = Vec::with_capacity(points.len());
+        let mut acc = f.one();
+        for p in &points {
+            prefix.push(acc);
+            if !f.is_zero(p.z) {
+                acc = f.mul(acc, p.z);
+            }
+        }
+        let mut inv = f.inv(acc);
+        let mut out = vec![None; points.len()];
+        for (i, p) in points.iter().enumerate().rev() {
+            if f.is_zero(p.z) {
+                continue;
+            }
+            let z_inv = f.mul(inv, prefix[i]);
+            inv = f.mul(inv, p.z);
+            let z_inv2 = f.square(z_inv);
+            out[i] = Some((f.mul(p.x, z_inv2), f.mul(p.y, f.mul(z_inv2, z_inv))));
+        }
+        out
+    }
+}
+
+// point in E(F_p[x] / psi_l), written as (X(x), Y(x) * y)
+#[derive(Clone)]
+struct RingPoint {
+    x: Poly,
+    y: Poly,
+}
+
+// division polynomials f_n, with psi_n = f_n for odd n and psi_n = y * f_n for even n
+struct DivisionPolys<'a> {
+    ring: PolyRing<'a>,
+    rhs_sq: Poly, // (x^3 + ax + b)^2
+    f: Vec<Poly>,
+}
+
+impl<'a> DivisionPolys<'a> {
+    fn new(ring: PolyRing<'a>, curve: &Curve, rhs: &[Fe]) -> Self {
+        let (fl, a, b) = (ring.f, curve.a, curve.b);
+        let c = |k: u64| fl.elem_u64(k);
+        let a_sq = fl.square(a);
+        // f_3 = 3x^4 + 6ax^2 + 12bx - a^2
+        let f3 = ring.trim(vec![fl.neg(a_sq), fl.mul(c(12), b), fl.mul(c(6), a), fl.zero(), c(3)]);
+        // f_4 = 4 (x^6 + 5ax^4 + 20bx^3 - 5a^2x^2 - 4abx - 8b^2 - a^3)
+        let f4 = ring.trim(vec![
+            fl.neg(fl.add(fl.mul(c(8), fl.square(b)), fl.mul(a_sq, a))),
+            fl.neg(fl.mul(c(4), fl.mul(a, b))),
+            fl.neg(fl.mul(c(5), a_sq)),
+            fl.mul(c(20), b),
+            fl.mul(c(5), a),
+            fl.zero(),
+            fl.one(),
+        ]);
+        let f = vec![Vec::new(), ring.with_coeffs(&[1]), ring.with_coeffs(&[2]), f3, ring.scale(&f4, c(4))];
+        DivisionPolys { ring, rhs_sq: ring.mul(rhs, rhs), f }
+    }
+
+    fn get(&mut self, n: usize) -> &[Fe] {
+        let r = self.ring;
+        while self.f.len() <= n {
+            let k = self.f.len();
+            let m = k / 2;
+            let f = &self.f;
+            let cube = |p: &Poly| r.mul(&r.mul(p, p), p);
+
+            let next = if k % 2 == 1 {
+                // psi_{2m+1} = psi_{m+2} psi_m^3 - psi_{m-1} psi_{m+1}^3
+                let lhs = r.mul(&f[m + 2], &cube(&f[m]));
+                let rhs = r.mul(&f[m - 1], &cube(&f[m + 1]));
+                if m.is_multiple_of(2) {
+                    r.sub(&r.mul(&self.rhs_sq, &lhs), &rhs)
+                } else {
+                    r.sub(&lhs, &r.mul(&self.rhs_sq, &rhs))
+                }
+            } else {
+                // psi_{2m} = psi_m (psi_{m+2} psi_{m-1}^2 - psi_{m-2} psi_{m+1}^2) / 2y
+                let lhs = r.mul(&f[m + 2], &r.mul(&f[m - 1], &f[m - 1]));
+                let rhs = r.mul(&f[m - 2], &r.mul(&f[m + 1], &f[m + 1]));
+                let half = r.f.inv(r.f.elem_u64(2));
+                r.scale(&r.mul(&f[m], &r.sub(&lhs, &rhs)), half)
+            };
+            self.f.push(next);
+        }
+        &self.f[n]
+    }
+}
+
+// Schoof's algorithm restricted to a single small prime l
+struct Schoof<'a> {
+    f: &'a Field,
+    ring: PolyRing<'a>,
+    a: Fe,
+    rhs: Poly,
+}
+
+impl<'a> Schoof<'a> {
+    fn new(curve: &Curve<'a>) -> Self {
+        let f = curve.f;
+        let ring = PolyRing::new(f);
+        let rhs = ring.trim(vec![curve.b, curve.a, f.zero(), f.one()]);
+        Schoof { f, ring, a: curve.a, rhs }
+    }
+
+    // t mod 2: the order is even iff x^3 + ax + b has a root in F_p
+    fn trace_mod_2(&self) -> u64 {
+        let q = QuotientRing::new(self.f, self.rhs.clone());
+        let xp = q.pow_x(self.f.modulus());
+        let g = self.ring.gcd(&self.ring.sub(&xp, &self.ring.x()), &self.rhs);
+        if g.len() > 1 { 0 } else { 1 }
+    }
+
+    fn add(&self, q: &QuotientRing, p1: &RingPoint, p2: &RingPoint) -> Option<RingPoint> {
+        let r = &self.ring;
+        let den = q.inv(&r.sub(&p2.x, &p1.x))?;
+        let slope = q.mul(&r.sub(&p2.y, &p1.y), &den);
+        Some(self.finish(q, &slope, p1, &p2.x))
+    }
+
+    fn double(&self, q: &QuotientRing, p: &RingPoint) -> Option<RingPoint> {
+        let r = &self.ring;
+        let x_sq = q.mul(&p.x, &p.x);
+        let num = r.add(&r.scale(&x_sq, self.f.elem_u64(3)), &r.constant(self.a));
+        let den = q.inv(&q.mul(&r.scale(&p.y, self.f.elem_u64(2)), &self.rhs))?;
+        let slope = q.mul(&num, &den);
+        Some(self.finish(q, &slope, p, &p.x))
+    }
+
+    // X3 = L^2 (x^3 + ax + b) - X1 - X2, Y3 = L (X1 - X3) - Y1
+    fn finish(&self, q: &QuotientRing, slope: &[Fe], p1: &RingPoint, x2: &[Fe]) -> RingPoint {
+        let r = &self.ring;
+        let slope_sq = q.mul(slope, slope);
+        let x3 = r.sub(&r.sub(&q.mul(&slope_sq, &self.rhs), &p1.x), x2);
+        let y3 = r.sub(&q.mul(slope, &r.sub(&p1.x, &x3)), &p1.y);
+        RingPoint { x: x3, y: y3 }
+    }
+
+    // [k](x, y) for 0 < k < l, from the division polynomials
+    fn multiple(&self, q: &QuotientRing, divs: &mut DivisionPolys, l: usize, k: usize) -> RingPoint {
+        let r = &self.ring;
+        // [l - k]P = -[k]P on the l-torsion
+        let kk = k.min(l - k);
+        let mut point = if kk == 1 {
+            RingPoint { x: r.x(), y: r.constant(self.f.one()) }
+        } else {
+            let fk = q.reduce(divs.get(kk));
+            let fk_sq = q.mul(&fk, &fk);
+            let mut x_num = q.mul(&q.reduce(divs.get(kk - 1)), &q.reduce(divs.get(kk + 1)));
+            let mut x_den = fk_sq.clone();
+            let mut y_den = r.scale(&q.mul(&fk_sq, &fk_sq), self.f.elem_u64(2));
+            if kk % 2 == 1 {
+                x_num = q.mul(&x_num, &self.rhs);
+            } else {
+                let rhs_sq = q.mul(&self.rhs, &self.rhs);
+                x_den = q.mul(&x_den, &self.rhs);
+                y_den = q.mul(&y_den, &rhs_sq);
+            }
+            let x_den_inv = q.inv(&x_den).expect("psi_k is a unit mod psi_l");
+            let y_den_inv = q.inv(&y_den).expect("psi_k is a unit mod psi_l");
+            RingPoint {
+                x: r.sub(&r.x(), &q.mul(&x_num, &x_den_inv)),
+                y: q.mul(&q.reduce(divs.get(2 * kk)), &y_den_inv),
+            }
+        };
+        if kk != k {
+            point.y = r.neg(&point.y);
+        }
+        point
+    }
+
+    // t mod l for an odd prime l != p
+    fn trace_mod_l(&self, divs: &mut DivisionPolys, l: usize) -> u64 {
+        let r = &self.ring;
+        let p = self.f.modulus();
+        let q = QuotientRing::new(self.f, divs.get(l).to_vec());
+
+        // Frobenius (x^p, y^p) and its square, with y^p = (x^3 + ax + b)^((p-1)/2) * y
+        let half = (p - BigUint::one()) >> 1usize;
+        let frob = RingPoint {
+            x: q.pow_x(p),
+            y: q.pow(&self.rhs, &half),
+        };
+
+        // h^p = h(x^p) for h in F_p[x], so the square comes from composition
+        let table = q.powers(&frob.x, q.deg().isqrt() + 1);
+        let frob2 = RingPoint {
+            x: q.compose(&frob.x, &table),
+            y: q.mul(&q.compose(&frob.y, &table), &frob.y),
+        };
+
+        let k = (p % BigUint::from(l)).to_usize().unwrap();
+        let kp = self.multiple(&q, divs, l, k);
+
+        match self.add(&q, &frob2, &kp) {
+            // pi^2 P + kP = t pi P on all of E[l], search for t
+            Some(target) => {
+                let mut acc = frob.clone();
+                for tau in 1..=(l - 1) / 2 {
+                    if tau == 2 {
+                        acc = self.double(&q, &frob).expect("pi P has odd order");
+                    } else if tau > 2 {
+                        acc = self.add(&q, &acc, &frob).expect("tau pi P != pi P");
+                    }
+                    if acc.x == target.x {
+                        let t = if acc.y == target.y { tau } else { l - tau };
+                        return t as u64;
+                    }
+                }
+                panic!("Schoof: no trace found mod {} for p = {}", l, p);
+            }
+            // pi^2 P = +-kP for some P, t is 0 or +-2w with w^2 = k
+            None => {
+                let Some(w) = (1..l).find(|w| w * w % l == k) else {
+                    return 0;
+                };
+                let wp = self.multiple(&q, divs, l, w);
+                if q.is_unit(&r.sub(&frob.x, &wp.x)) {
+                    return 0;
+                }
+                let t = if q.is_unit(&r.sub(&frob.y, &wp.y)) { l - 2 * w % l } else { 2 * w % l };
+                (t % l) as u64
+            }
+        }
+    }
+}
+
+// smallest N >= lo with N = residue mod m
+fn first_in_class(lo: &BigUint, residue: &BigUint, m: &BigUint) -> BigUint {
+    let offset = (residue + m - lo % m) % m;
+    lo + offset
+}
+
+// baby-step giant-step for N = first + j * step (0 <= j < count) with [N]P = O
+fn bsgs(curve: &Curve, point: Point, first: &BigUint, step: &BigUint, count: u64) -> Option<BigUint> {
+    let f = curve.f;
+    let m = count.sqrt() + 1;
+
+    // baby steps [i step]P, keyed by x so a hit means [i step]P = +-S
+    let step_point = curve.mul(step, point);
+    let mut baby: HashMap<u64, u64> = HashMap::with_capacity(m as usize);
+    for (i, b) in curve.chain(None, step_point, m).iter().enumerate() {
+        if let Some((x, _)) = b {
+            baby.entry(x.low_limb()).or_insert(i as u64);
+        }
+    }
+
+    // giant steps S = [first]P + [g m step]P
+    let giant = curve.mul(&(BigUint::from(m) * step), point);
+    let mut cur = curve.mul(first, point);
+    let mut g = 0;
+    while g <= m {
+        let n = BSGS_BATCH.min(m + 1 - g);
+        let batch = curve.chain(cur, giant, n + 1);
+        for (offset, s) in batch[..n as usize].iter().enumerate() {
+            let gm = (g + offset as u64) * m;
+            let j = match s {
+                None => Some(gm),
+                Some((x, y)) => baby.get(&x.low_limb()).and_then(|&i| {
+                    let (bx, by) = curve.mul(&(BigUint::from(i) * step), point)?;
+                    if bx != *x {
+                        None
+                    } else if by == f.neg(*y) {
+                        Some(gm + i)
+                    } else {
+                        gm.checked_sub(i)
+                    }
+                }),
+            };
+            if let Some(j) = j.filter(|&j| j < count) {
+                return Some(first + BigUint::from(j) * step);
+            }
+        }
+        cur = batch[n as usize];
+        g += n;
+    }
+    None
+}
+
+// first point on y^2 = x^3 + ax + b by increasing x, skipping those of order 2
+fn some_point(f: &Field, a: &BigUint, b: &BigUint) -> Point {
+    let p = f.modulus();
+    let mut x = BigUint::zero();
+    loop {
+        let rhs = (x.modpow(&BigUint::from(3u32), p) + a * &x + b) % p;
+        if let Some(y) = sqrt_mod(&rhs, p).filter(|y| !y.is_zero()) {
+            return Some((f.elem(&x), f.elem(&y)));
+        }
+        x += BigUint::one();
+    }
+}
+
+// Hasse: |t| <= 2 sqrt(p), rounded up
+fn trace_bound(p: &BigUint) -> BigUint {
+    (p << 2usize).sqrt() + BigUint::one()
+}
+
+// #E = p + 1 - t. Schoof finds t mod l for the small primes l until at most 2^max_range_bits
+// candidates remain, 2^min_range_bits while l <= SCHOOF_CHEAP_L, and `give_up(l, t_l)` can end the
+// count there. Baby-step giant-step then takes the first candidate N with [N]point = O, which is
+// #E whenever point has order above 4 sqrt(p)
+fn count_points(
+    curve: &Curve,
+    point: Point,
+    max_range_bits: u64,
+    min_range_bits: u64,
+    mut give_up: impl FnMut(u64, u64) -> bool,
+) -> Option<BigUint> {
+    let p = curve.f.modulus();
+    let bound = trace_bound(p);
+    let width = &bound << 1usize;
+
+    // t mod m from Schoof, m = product of the small primes used
+    let schoof = Schoof::new(curve);
+    let mut divs = DivisionPolys::new(schoof.ring, curve, &schoof.rhs);
+    let mut t = BigUint::zero();
+    let mut m = BigUint::one();
+    for &l in SMALL_PRIMES_U16.iter() {
+        let l = l as u64;
+        let range_bits = (&width / &m).bits();
+        if range_bits <= min_range_bits || (range_bits <= max_range_bits && l > SCHOOF_CHEAP_L) {
+            break;
+        }
+        if BigUint::from(l) == *p {
+            continue;
+        }
+        let t_l = if l == 2 {
+            schoof.trace_mod_2()
+        } else {
+            schoof.trace_mod_l(&mut divs, l as usize)
+        };
+        if give_up(l, t_l) {
+            return None;
+        }
+
+        // CRT: t = t_l mod l
+        let t_mod_l = (&t % l).to_u64().unwrap();
+        let m_inv = BigUint::from((&m % l).to_u64().unwrap()).modpow(&BigUint::from(l - 2), &BigUint::from(l));
+        let lift = (t_l + l - t_mod_l) % l * m_inv.to_u64().unwrap() % l;
+        t += &m * lift;
+        m *= l;
+    }
+
+    // #E = p + 1 - t lies in [p + 1 - bound, p + 1 + bound]
+    let lo = p + BigUint::one() - &bound;
+    let residue = (p + BigUint::one() + &m - &t % &m) % &m;
+    let first = first_in_class(&lo, &residue, &m);
+    let count = (&width / &m).to_u64().unwrap() + 2;
+    bsgs(curve, point, &first, &m, count)
+}
+
+// count points on y^2 = x^3 + ax + b over F_p and split off a prime-order subgroup,
+// None if the order has no prime factor n with #E / n <= MAX_COFACTOR
+pub fn prime_order_subgroup(p: &BigUint, primality: &Primality) -> Option<CurveOrder> {
+    let f = Field::new(p);
+    let (a, b) = (BigUint::from(CURVE_A), BigUint::from(CURVE_B));
+    let curve = Curve::new(&f, &a, &b);
+    let point = some_point(&f, &a, &b);
+
+    // l divides #E = p + 1 - t, too big to be part of the cofactor
+    let order = count_points(&curve, point, BSGS_RANGE_BITS, BSGS_MIN_RANGE_BITS, |l, t_l| {
+        l > MAX_COFACTOR as u64 && t_l == ((p + BigUint::one()) % l).to_u64().unwrap()
+    })?;
+
+    // order = h * n with h small and n prime
+    let mut n = order.clone();
+    let mut cofactor = 1u32;
+    for &q in SMALL_PRIMES_U16.iter().take_while(|&&q| q as u32 <= MAX_COFACTOR) {
+        while (&n % q).is_zero() {
+            n /= q;
+            cofactor *= q as u32;
+        }
+    }
+    if cofactor > MAX_COFACTOR || !primality.is_prime(&n) {
+        return None;
+    }
+
+    // n | ord(P) and n > 4 sqrt(p) makes #E the only multiple of n in the Hasse interval
+    if n <= trace_bound(p) << 1usize || curve.mul(&BigUint::from(cofactor), point).is_none() {
+        return None;
+    }
+
+    Some(CurveOrder {
+        order,
+        subgroup_order: n,
+        cofactor,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::is_prime::PrimalityTest;
+
+    const BPSW: Primality = Primality { test: PrimalityTest::Bpsw, extra_rounds: 0, seed: 0 };
+
+    // #E by Euler's criterion on every x, small p only
+    fn brute_force(p: u64, a: u64, b: u64) -> u64 {
+        let pow = |mut base: u64, mut exp: u64| {
+            let mut acc = 1;
+            base %= p;
+            while exp > 0 {
+                if exp & 1 == 1 {
+                    acc = acc * base % p;
+                }
+                base = base * base % p;
+                exp >>= 1;
+            }
+            acc
+        };
+        1 + (0..p)
+            .map(|x| match (x * x % p * x + a * x + b) % p {
+                0 => 1,
+                rhs if pow(rhs, (p - 1) / 2) == 1 => 2,
+                _ => 0,
+            })
+            .sum::<u64>()
+    }
+
+    // the trace t = p + 1 - #E mod l
+    fn residue(p: u64, order: u64, l: u64) -> u64 {
+        ((p + 1) as i64 - order as i64).rem_euclid(l as i64) as u64
+    }
+
+    // y^2 = x^3 + 2x + 3 as in the table, j = 0, j = 1728, and two curves whose cubic has the root
+    // x = 1 so they have a point of order 2
+    fn curves(p: u64) -> [(u64, u64); 5] {
+        [(2, 3), (0, 7), (5, 0), (3, p - 4), (p - 1, 0)]
+    }
+
+    #[test]
+    fn schoof_traces_match_brute_force() {
+        for p in [1009u64, 2003, 10007] {
+            let f = Field::new(&BigUint::from(p));
+            for (a, b) in curves(p) {
+                let order = brute_force(p, a, b);
+                let curve = Curve::new(&f, &BigUint::from(a), &BigUint::from(b));
+                let schoof = Schoof::new(&curve);
+                let mut divs = DivisionPolys::new(schoof.ring, &curve, &schoof.rhs);
+                assert_eq!(schoof.trace_mod_2(), residue(p, order, 2), "p = {}, a = {}, b = {}", p, a, b);
+                for l in [3, 5, 7, 11, 13] {
+                    let t_l = schoof.trace_mod_l(&mut divs, l as usize);
+                    assert_eq!(t_l, residue(p, order, l), "l = {}, p = {}, a = {}, b = {}", l, p, a, b);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn counts_match_brute_force() {
+        for p in [1009u64, 2003, 10007] {
+            let f = Field::new(&BigUint::from(p));
+            for (a, b) in curves(p) {
+                let (big_a, big_b) = (BigUint::from(a), BigUint::from(b));
+                let curve = Curve::new(&f, &big_a, &big_b);
+                let point = some_point(&f, &big_a, &big_b);
+                let order = BigUint::from(brute_force(p, a, b));
+                // the first multiple of ord(point) is only #E when no other fits in the Hasse interval
+                let mut point_order = order.clone();
+                for q in 2..=p {
+                    while (&point_order % q).is_zero() && curve.mul(&(&point_order / q), point).is_none() {
+                        point_order /= q;
+                    }
+                }
+                if point_order <= trace_bound(&BigUint::from(p)) << 1usize {
+                    continue;
+                }
+                // Schoof until one candidate is left, and BSGS alone
+                assert_eq!(count_points(&curve, point, 0, 0, |_, _| false), Some(order.clone()), "p = {}", p);
+                assert_eq!(count_points(&curve, point, u64::MAX, u64::MAX, |_, _| false), Some(order), "p = {}", p);
+            }
+        }
+    }
+
+    // SEC 2 secp112r1, cofactor 1
+    #[test]
+    fn published_order() {
+        let hex = |s| BigUint::parse_bytes(s, 16).unwrap();
+        let p = hex(b"db7c2abf62e35e668076bead208b");
+        let (a, b) = (hex(b"db7c2abf62e35e668076bead2088"), hex(b"659ef8ba043916eede8911702b22"));
+        let n = hex(b"db7c2abf62e35e7628dfac6561c5");
+        let f = Field::new(&p);
+        let curve = Curve::new(&f, &a, &b);
+        let point = some_point(&f, &a, &b);
+        assert_eq!(count_points(&curve, point, BSGS_RANGE_BITS, BSGS_MIN_RANGE_BITS, |_, _| false), Some(n));
+    }
+
+    #[test]
+    fn schoof_and_bsgs_agree() {
+        for bits in [40u64, 48, 56, 64] {
+            let mut p = (BigUint::one() << (bits - 1)) + BigUint::from(1_000_003u32);
+            while !BPSW.is_prime(&p) {
+                p += 2u32;
+            }
+            let f = Field::new(&p);
+            let (a, b) = (BigUint::from(CURVE_A), BigUint::from(CURVE_B));
+            let curve = Curve::new(&f, &a, &b);
+            let point = some_point(&f, &a, &b);
+            let bsgs = count_points(&curve, point, u64::MAX, u64::MAX, |_, _| false).unwrap();
+            let schoof = count_points(&curve, point, BSGS_RANGE_BITS, BSGS_MIN_RANGE_BITS, |_, _| false);
+            assert_eq!(schoof.as_ref(), Some(&bsgs), "{} bits", bits);
+
+            // and a trace Schoof finds on its own, t = p + 1 - #E mod 13
+            let schoof = Schoof::new(&curve);
+            let mut divs = DivisionPolys::new(schoof.ring, &curve, &schoof.rhs);
+            let t = ((&p + 1u32) % 13u32 + 13u32 - &bsgs % 13u32) % 13u32;
+            assert_eq!(schoof.trace_mod_l(&mut divs, 13), t.to_u64().unwrap(), "{} bits", bits);
+        }
+    }
+
+    #[test]
+    fn subgroup_of_the_table_curve() {
+        let p = BigUint::from(0xbf33u32);
+        let subgroup = prime_order_subgroup(&p, &BPSW).unwrap();
+        assert_eq!(subgroup.order, BigUint::from(brute_force(0xbf33, CURVE_A as u64, CURVE_B as u64)));
+        assert_eq!(subgroup.order, &subgroup.subgroup_order * subgroup.cofactor);
+    }
+}
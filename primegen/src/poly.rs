@@ -0,0 +1,361 @@
+use num_bigint::BigUint;
+
+use crate::field::{Fe, Field};
+
+// below this many coefficients schoolbook multiplication is faster
+const KARATSUBA_CUTOFF: usize = 24;
+
+// polynomial over GF(p), coefficients lowest degree first, no trailing zeros
+pub type Poly = Vec<Fe>;
+
+// F_p[x] arithmetic
+#[derive(Clone, Copy)]
+pub struct PolyRing<'a> {
+    pub f: &'a Field,
+}
+
+impl<'a> PolyRing<'a> {
+    pub fn new(f: &'a Field) -> Self {
+        PolyRing { f }
+    }
+
+    pub fn trim(&self, mut a: Poly) -> Poly {
+        while a.last().is_some_and(|&c| self.f.is_zero(c)) {
+            a.pop();
+        }
+        a
+    }
+
+    pub fn constant(&self, c: Fe) -> Poly {
+        self.trim(vec![c])
+    }
+
+    pub fn x(&self) -> Poly {
+        vec![self.f.zero(), self.f.one()]
+    }
+
+    // build from small signed integer coefficients
+    pub fn with_coeffs(&self, coeffs: &[i64]) -> Poly {
+        let out = coeffs
+            .iter()
+            .map(|&c| {
+                let v = self.f.elem_u64(c.unsigned_abs());
+                if c < 0 { self.f.neg(v) } else { v }
+            })
+            .collect();
+        self.trim(out)
+    }
+
+    pub fn degree(&self, a: &[Fe]) -> Option<usize> {
+        a.len().checked_sub(1)
+    }
+
+    pub fn is_one(&self, a: &[Fe]) -> bool {
+        a.len() == 1 && a[0] == self.f.one()
+    }
+
+    pub fn add(&self, a: &[Fe], b: &[Fe]) -> Poly {
+        let (long, short) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+        let mut out = long.to_vec();
+        for (o, &c) in out.iter_mut().zip(short) {
+            *o = self.f.add(*o, c);
+        }
+        self.trim(out)
+    }
+
+    pub fn sub(&self, a: &[Fe], b: &[Fe]) -> Poly {
+        let mut out = a.to_vec();
+        if out.len() < b.len() {
+            out.resize(b.len(), self.f.zero());
+        }
+        for (o, &c) in out.iter_mut().zip(b) {
+            *o = self.f.sub(*o, c);
+        }
+        self.trim(out)
+    }
+
+    pub fn neg(&self, a: &[Fe]) -> Poly {
+        a.iter().map(|&c| self.f.neg(c)).collect()
+    }
+
+    pub fn scale(&self, a: &[Fe], c: Fe) -> Poly {
+        self.trim(a.iter().map(|&v| self.f.mul(v, c)).collect())
+    }
+
+    pub fn mul(&self, a: &[Fe], b: &[Fe]) -> Poly {
+        if a.is_empty() || b.is_empty() {
+            return Vec::new();
+        }
+        self.trim(self.mul_slices(a, b))
+    }
+
+    fn mul_schoolbook(&self, a: &[Fe], b: &[Fe]) -> Vec<Fe> {
+        let mut out = vec![self.f.zero(); a.len() + b.len() - 1];
+        for (i, &ai) in a.iter().enumerate() {
+            for (j, &bj) in b.iter().enumerate() {
+                out[i + j] = self.f.add(out[i + j], self.f.mul(ai, bj));
+            }
+        }
+        out
+    }
+
+    fn add_into(&self, dst: &mut [Fe], src: &[Fe]) {
+        for (d, &s) in dst.iter_mut().zip(src) {
+            *d = self.f.add(*d, s);
+        }
+    }
+
+    fn sub_into(&self, dst: &mut [Fe], src: &[Fe]) {
+        for (d, &s) in dst.iter_mut().zip(src) {
+            *d = self.f.sub(*d, s);
+        }
+    }
+
+    fn sum_halves(&self, lo: &[Fe], hi: &[Fe]) -> Vec<Fe> {
+        let mut out = lo.to_vec();
+        if out.len() < hi.len() {
+            out.resize(hi.len(), self.f.zero());
+        }
+        self.add_into(&mut out, hi);
+        out
+    }
+
+    // Karatsuba multiplication, result has a.len() + b.len() - 1 coefficients
+    fn mul_slices(&self, a: &[Fe], b: &[Fe]) -> Vec<Fe> {
+        if a.len() < KARATSUBA_CUTOFF || b.len() < KARATSUBA_CUTOFF {
+            return self.mul_schoolbook(a, b);
+        }
+
+        let half = a.len().max(b.len()) / 2;
+        let mut out = vec![self.f.zero(); a.len() + b.len() - 1];
+
+        // unbalanced operands: split only the longer one
+        if a.len() <= half || b.len() <= half {
+            let (long, short) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+            let lo = self.mul_slices(&long[..half], short);
+            let hi = self.mul_slices(&long[half..], short);
+            self.add_into(&mut out, &lo);
+            self.add_into(&mut out[half..], &hi);
+            return out;
+        }
+
+        let (a0, a1) = a.split_at(half);
+        let (b0, b1) = b.split_at(half);
+        let z0 = self.mul_slices(a0, b0);
+        let z2 = self.mul_slices(a1, b1);
+        let mut z1 = self.mul_slices(&self.sum_halves(a0, a1), &self.sum_halves(b0, b1));
+        self.sub_into(&mut z1, &z0);
+        self.sub_into(&mut z1, &z2);
+
+        self.add_into(&mut out, &z0);
+        self.add_into(&mut out[half..], &z1);
+        self.add_into(&mut out[2 * half..], &z2);
+        out
+    }
+
+    // schoolbook long division, b must be non-zero
+    pub fn divrem(&self, a: &[Fe], b: &[Fe]) -> (Poly, Poly) {
+        assert!(!b.is_empty(), "Polynomial division by zero");
+        if a.len() < b.len() {
+            return (Vec::new(), a.to_vec());
+        }
+
+        let lead_inv = self.f.inv(*b.last().unwrap());
+        let mut rem = a.to_vec();
+        let mut quot = vec![self.f.zero(); a.len() - b.len() + 1];
+
+        for i in (0..quot.len()).rev() {
+            let c = self.f.mul(rem[i + b.len() - 1], lead_inv);
+            quot[i] = c;
+            if self.f.is_zero(c) {
+                continue;
+            }
+            for (j, &bj) in b.iter().enumerate() {
+                rem[i + j] = self.f.sub(rem[i + j], self.f.mul(c, bj));
+            }
+        }
+        rem.truncate(b.len() - 1);
+        (self.trim(quot), self.trim(rem))
+    }
+
+    pub fn monic(&self, a: &[Fe]) -> Poly {
+        match a.last() {
+            Some(&lead) => self.scale(a, self.f.inv(lead)),
+            None => Vec::new(),
+        }
+    }
+
+    // monic greatest common divisor
+    pub fn gcd(&self, a: &[Fe], b: &[Fe]) -> Poly {
+        let mut r0 = a.to_vec();
+        let mut r1 = b.to_vec();
+        while !r1.is_empty() {
+            let (_, r) = self.divrem(&r0, &r1);
+            r0 = r1;
+            r1 = r;
+        }
+        self.monic(&r0)
+    }
+}
+
+// F_p[x] / (m) for a monic modulus m, reduction by a precomputed Barrett inverse
+pub struct QuotientRing<'a> {
+    pub ring: PolyRing<'a>,
+    modulus: Poly,
+    inv_rev: Poly, // reverse(m)^-1 mod x^(deg m - 1)
+}
+
+impl<'a> QuotientRing<'a> {
+    pub fn new(f: &'a Field, modulus: Poly) -> Self {
+        let ring = PolyRing::new(f);
+        let modulus = ring.monic(&modulus);
+        let deg = ring.degree(&modulus).expect("Zero modulus");
+        let rev: Poly = modulus.iter().rev().copied().collect();
+
+        // Newton iteration for the power series inverse of rev(m)
+        let prec = deg.saturating_sub(1).max(1);
+        let two = ring.constant(f.elem_u64(2));
+        let mut inv = vec![f.one()];
+        let mut len = 1;
+        while len < prec {
+            len = (2 * len).min(prec);
+            let mut e = ring.mul(&rev[..len.min(rev.len())], &inv);
+            e.truncate(len);
+            let mut next = ring.mul(&inv, &ring.sub(&two, &ring.trim(e)));
+            next.truncate(len);
+            inv = ring.trim(next);
+        }
+
+        QuotientRing { ring, modulus, inv_rev: inv }
+    }
+
+    pub fn deg(&self) -> usize {
+        self.modulus.len() - 1
+    }
+
+    pub fn reduce(&self, a: &[Fe]) -> Poly {
+        let d = self.deg();
+        if a.len() <= d {
+            return a.to_vec();
+        }
+        let n = a.len() - 1;
+        if n > 2 * d - 2 || d < 2 {
+            return self.ring.divrem(a, &self.modulus).1;
+        }
+
+        // quotient degree k, reversed quotient = reversed top of a * inv_rev mod x^(k+1)
+        let k = n - d;
+        let top: Poly = a[d..].iter().rev().copied().collect();
+        let mut q_rev = self.ring.mul(&top, &self.inv_rev);
+        q_rev.resize(k + 1, self.ring.f.zero());
+        let q: Poly = self.ring.trim(q_rev.into_iter().rev().collect());
+
+        let qm = self.ring.mul(&q, &self.modulus);
+        let mut r = a[..d].to_vec();
+        self.ring.sub_into(&mut r, &qm[..d.min(qm.len())]);
+        self.ring.trim(r)
+    }
+
+    pub fn mul(&self, a: &[Fe], b: &[Fe]) -> Poly {
+        self.reduce(&self.ring.mul(a, b))
+    }
+
+    // x^e mod m, multiplying by x is just a shift
+    pub fn pow_x(&self, exp: &BigUint) -> Poly {
+        let f = self.ring.f;
+        let d = self.deg();
+        let mut result = self.ring.constant(f.one());
+        for i in (0..exp.bits()).rev() {
+            result = self.mul(&result, &result);
+            if exp.bit(i) {
+                result.insert(0, f.zero());
+                if result.len() > d {
+                    let lead = result.pop().unwrap();
+                    for (r, &m) in result.iter_mut().zip(&self.modulus) {
+                        *r = f.sub(*r, f.mul(lead, m));
+                    }
+                }
+                result = self.ring.trim(result);
+            }
+        }
+        result
+    }
+
+    // fixed 4-bit window exponentiation
+    pub fn pow(&self, base: &[Fe], exp: &BigUint) -> Poly {
+        let mut table = vec![self.ring.constant(self.ring.f.one()), self.reduce(base)];
+        for i in 2..16 {
+            table.push(self.mul(&table[i - 1], &table[1]));
+        }
+
+        let mut result = table[0].clone();
+        let windows = exp.bits().div_ceil(4);
+        for w in (0..windows).rev() {
+            for _ in 0..4 {
+                result = self.mul(&result, &result);
+            }
+            let nibble = (0..4).fold(0, |acc, b| acc | ((exp.bit(4 * w + b) as usize) << b));
+            if nibble != 0 {
+                result = self.mul(&result, &table[nibble]);
+            }
+        }
+        result
+    }
+
+    // g^0 ..= g^k, the baby steps for compose()
+    pub fn powers(&self, g: &[Fe], k: usize) -> Vec<Poly> {
+        let mut table = vec![self.ring.constant(self.ring.f.one()), self.reduce(g)];
+        for i in 2..=k {
+            table.push(self.mul(&table[i - 1], &table[1]));
+        }
+        table
+    }
+
+    // h(g) mod m by Brent-Kung, splitting h into blocks of k coefficients
+    pub fn compose(&self, h: &[Fe], table: &[Poly]) -> Poly {
+        let f = self.ring.f;
+        let k = table.len() - 1;
+        let mut result = Vec::new();
+        for block in h.chunks(k).rev() {
+            let mut acc = vec![f.zero(); self.deg()];
+            for (&c, g_i) in block.iter().zip(table) {
+                if f.is_zero(c) {
+                    continue;
+                }
+                for (a, &v) in acc.iter_mut().zip(g_i) {
+                    *a = f.add(*a, f.mul(c, v));
+                }
+            }
+            result = self.ring.add(&self.mul(&result, &table[k]), &self.ring.trim(acc));
+        }
+        result
+    }
+
+    // true if a shares no factor with the modulus
+    pub fn is_unit(&self, a: &[Fe]) -> bool {
+        self.ring.is_one(&self.ring.gcd(&self.modulus, &self.reduce(a)))
+    }
+
+    // inverse by the extended Euclidean algorithm, None if a is not a unit
+    pub fn inv(&self, a: &[Fe]) -> Option<Poly> {
+        let mut r0 = self.modulus.clone();
+        let mut r1 = self.reduce(a);
+        let mut s0: Poly = Vec::new();
+        let mut s1 = self.ring.constant(self.ring.f.one());
+
+        while !r1.is_empty() {
+            let (q, r) = self.ring.divrem(&r0, &r1);
+            let s = self.ring.sub(&s0, &self.ring.mul(&q, &s1));
+            r0 = r1;
+            r1 = r;
+            s0 = s1;
+            s1 = s;
+        }
+
+        if r0.len() != 1 {
+            return None;
+        }
+        let scale = self.ring.f.inv(r0[0]);
+        Some(self.reduce(&self.ring.scale(&s0, scale)))
+    }
+}
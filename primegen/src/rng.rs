@@ -0,0 +1,53 @@
+use hkdf::Hkdf;
+use num_bigint::BigUint;
+use num_traits::{One, Zero};
+use rand_chacha::rand_core::{RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use sha2::Sha256;
+
+// bump when the derivation changes, old lookup tables are not reproducible across versions
+const DOMAIN: &[u8] = b"primegen/param-rng/v1";
+
+// deterministic CSPRNG for a single generated parameter
+//
+// the ChaCha20 key is HKDF-SHA256(seed, "algorithm/bits/trial/field"), so every
+// (algorithm, key size, trial, field) gets its own independent stream and adding
+// trials or reordering draws does not shift any other value
+pub struct ParamRng(ChaCha20Rng);
+
+impl ParamRng {
+    pub fn new(seed: u32, algorithm: &str, bits: u16, trial: u8, field: &str) -> Self {
+        let info = format!("{}/{}/{}/{}", algorithm, bits, trial, field);
+        let hk = Hkdf::<Sha256>::new(Some(DOMAIN), &seed.to_le_bytes());
+        let mut key = [0u8; 32];
+        hk.expand(info.as_bytes(), &mut key)
+            .expect("32 bytes is a valid HKDF-SHA256 output length");
+        ParamRng(ChaCha20Rng::from_seed(key))
+    }
+
+    // uniform in [0, max), rejection sampling on the bit length of max so there is no modulo bias
+    pub fn below(&mut self, max: &BigUint) -> BigUint {
+        assert!(!max.is_zero(), "Empty range");
+        let bits = (max - BigUint::one()).bits();
+        if bits == 0 {
+            return BigUint::zero();
+        }
+
+        let mut bytes = vec![0u8; bits.div_ceil(8) as usize];
+        let top_mask = 0xffu8 >> (bytes.len() as u64 * 8 - bits);
+        loop {
+            self.0.fill_bytes(&mut bytes);
+            bytes[0] &= top_mask;
+            let candidate = BigUint::from_bytes_be(&bytes);
+            if &candidate < max {
+                return candidate;
+            }
+        }
+    }
+
+    // uniform in [min, max]
+    pub fn range(&mut self, min: &BigUint, max: &BigUint) -> BigUint {
+        assert!(min <= max, "Empty range");
+        min + self.below(&(max - min + BigUint::one()))
+    }
+}
@@ -0,0 +1,161 @@
+use clap::ValueEnum;
+use num_bigint::BigUint;
+use num_traits::ToPrimitive;
+use std::sync::LazyLock;
+
+// odd candidates sieved at once, big enough that the per-window residues are cheap
+pub const WINDOW: usize = 4096;
+// sieving primes go well past SMALL_PRIMES_U16, every extra composite removed saves a Miller-Rabin
+const SIEVE_LIMIT: usize = 1 << 16;
+// never sieve by less than SMALL_PRIMES_U16 covers, survivors skip that trial division
+const MIN_SIEVE_BOUND: u64 = 1 << 11;
+// below this a window holds the whole range and its residues cost more than dividing the few
+// candidates tried, so smaller primes are searched by trial division whatever was asked for
+pub const MIN_SIEVE_BITS: u16 = 24;
+
+// odd primes below SIEVE_LIMIT (Eratosthenes)
+static SIEVE_PRIMES: LazyLock<Vec<u64>> = LazyLock::new(|| {
+    let mut is_prime = vec![true; SIEVE_LIMIT];
+    let mut primes = Vec::new();
+    for n in (3..SIEVE_LIMIT).step_by(2) {
+        if is_prime[n] {
+            primes.push(n as u64);
+            (n * n..SIEVE_LIMIT).step_by(n).for_each(|m| is_prime[m] = false);
+        }
+    }
+    primes
+});
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum PrimeSearch {
+    /// Sieve a window of candidates by the small primes, then test only the survivors
+    Sieve,
+    /// Trial-divide every candidate in BigUint arithmetic (the original search)
+    TrialDivision,
+}
+
+impl PrimeSearch {
+    // what actually searches for a prime of `bits`
+    pub fn for_bits(self, bits: u16) -> PrimeSearch {
+        if bits < MIN_SIEVE_BITS { PrimeSearch::TrialDivision } else { self }
+    }
+
+    pub fn other(self) -> PrimeSearch {
+        match self {
+            PrimeSearch::Sieve => PrimeSearch::TrialDivision,
+            PrimeSearch::TrialDivision => PrimeSearch::Sieve,
+        }
+    }
+}
+
+// what the candidates in a window have to satisfy
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Form {
+    // c itself has no small factor
+    Prime,
+    // c and 2c + 1 both have no small factor, c is the Sophie Germain half of a safe prime
+    SafePrime,
+}
+
+// modular inverse of 2 mod an odd prime r
+fn half(r: u64) -> u64 {
+    r.div_ceil(2)
+}
+
+// clears every r-th flag from `first`, except where `value(i)` is r itself
+fn strike(survivors: &mut [bool], first: u64, r: u64, value: impl Fn(u64) -> Option<u64>) {
+    let mut i = first as usize;
+    while i < survivors.len() {
+        if value(i as u64) != Some(r) {
+            survivors[i] = false;
+        }
+        i += r as usize;
+    }
+}
+
+// start mod r for every r, reducing the big number once per pack of primes that fits a u64
+fn residues(start: &BigUint, primes: &[u64]) -> Vec<u64> {
+    let mut out = Vec::with_capacity(primes.len());
+    let mut i = 0;
+    while i < primes.len() {
+        let mut product = primes[i];
+        let mut j = i + 1;
+        while j < primes.len()
+            && let Some(p) = product.checked_mul(primes[j])
+        {
+            product = p;
+            j += 1;
+        }
+        let rem = (start % product).to_u64().unwrap_or(0);
+        out.extend(primes[i..j].iter().map(|&r| rem % r));
+        i = j;
+    }
+    out
+}
+
+// survivors among the odd candidates start, start + 2, ..., start + 2(len - 1)
+//
+// removes at least everything trial division by SMALL_PRIMES_U16 would, a value equal to a
+// sieving prime survives so the first prime found is the same
+pub fn sieve_window(start: &BigUint, len: usize, form: Form) -> Vec<bool> {
+    debug_assert!(start.bit(0), "Sieve start must be odd");
+    let mut survivors = vec![true; len];
+
+    // larger candidates make Miller-Rabin dearer, so they are worth sieving further (~4 bits^2)
+    let bits = start.bits();
+    let bound = bits.saturating_mul(bits).saturating_mul(4).clamp(MIN_SIEVE_BOUND, SIEVE_LIMIT as u64);
+    let primes = &SIEVE_PRIMES[..SIEVE_PRIMES.partition_point(|&r| r < bound)];
+
+    // only a window starting below 2^64 can contain a small prime itself
+    let small_start = start.to_u64();
+    let candidate = |i: u64| small_start.and_then(|s| s.checked_add(2 * i));
+
+    for (&r, s) in primes.iter().zip(residues(start, primes)) {
+        let inv2 = half(r);
+
+        // start + 2i = 0  =>  i = -start / 2
+        let i_c = (r - s) % r * inv2 % r;
+        strike(&mut survivors, i_c, r, candidate);
+
+        if form == Form::SafePrime {
+            // 2(start + 2i) + 1 = 0  =>  i = (-1/2 - start) / 2
+            let i_p = (2 * r - inv2 - s) % r * inv2 % r;
+            strike(&mut survivors, i_p, r, |i| candidate(i).and_then(|c| c.checked_mul(2)).map(|c| c | 1));
+        }
+    }
+    survivors
+}
+
+// first odd candidate from `start` that survives the sieve and `accept`, wrapping from `max`
+// back to `min | 1` like the trial-division search, None after `limit` candidates
+pub fn search(
+    start: &BigUint,
+    min: &BigUint,
+    max: &BigUint,
+    form: Form,
+    limit: u32,
+    accept: impl Fn(&BigUint) -> bool,
+) -> Option<BigUint> {
+    let mut candidate = start.clone();
+    let mut remaining = limit as usize;
+    while remaining > 0 {
+        if candidate > *max {
+            candidate = min | BigUint::from(1u32);
+        }
+
+        // a window never crosses max, so the wrap happens at the same candidate as before
+        let to_max = ((max - &candidate) >> 1usize).to_usize().unwrap_or(usize::MAX).saturating_add(1);
+        let len = WINDOW.min(remaining).min(to_max);
+        let survivors = sieve_window(&candidate, len, form);
+        for (i, _) in survivors.iter().enumerate().filter(|(_, s)| **s) {
+            let c = &candidate + 2 * i as u64;
+            if accept(&c) {
+                return Some(c);
+            }
+        }
+
+        candidate += 2 * len as u64;
+        remaining -= len;
+    }
+    None
+}
@@ -0,0 +1,607 @@
+use num_bigint::BigUint;
+use num_integer::Integer;
+use num_traits::{One, Zero};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::comb::comb_table;
+use crate::curve::scalar_mul;
+use crate::is_prime::{Primality, PrimalityTest};
+use crate::point_count::MAX_COFACTOR;
+
+// audits always use the strongest test, whatever the table was generated with
+const PRIMALITY: Primality = Primality { test: PrimalityTest::Bpsw, extra_rounds: 0, seed: 0 };
+
+// tokens of the small Rust subset primegen writes into lookup.rs
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Number(String),
+    Str(String),
+    Punct(char),
+}
+
+fn tokenize(src: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = src.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '/' {
+            chars.next();
+            if chars.peek() != Some(&'/') {
+                return Err("unexpected '/'".to_string());
+            }
+            // line comment
+            for c in chars.by_ref() {
+                if c == '\n' {
+                    break;
+                }
+            }
+        } else if c == '"' {
+            chars.next();
+            let mut s = String::new();
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some(c) => s.push(c),
+                    None => return Err("unterminated string".to_string()),
+                }
+            }
+            tokens.push(Token::Str(s));
+        } else if c.is_ascii_digit() {
+            let mut s = String::new();
+            while let Some(&c) = chars.peek().filter(|c| c.is_ascii_alphanumeric() || **c == '_') {
+                s.push(c);
+                chars.next();
+            }
+            tokens.push(Token::Number(s));
+        } else if c.is_alphabetic() || c == '_' {
+            let mut s = String::new();
+            while let Some(&c) = chars.peek().filter(|c| c.is_alphanumeric() || **c == '_') {
+                s.push(c);
+                chars.next();
+            }
+            tokens.push(Token::Ident(s));
+        } else {
+            tokens.push(Token::Punct(c));
+            chars.next();
+        }
+    }
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone)]
+enum Value {
+    Int(BigUint),
+    Const(String),
+    Struct(Vec<(String, Value)>),
+    List(Vec<Value>),
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Result<Token, String> {
+        let token = self.tokens.get(self.pos).cloned().ok_or("unexpected end of file")?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        match self.next()? {
+            Token::Punct(p) if p == c => Ok(()),
+            t => Err(format!("expected '{}', found {:?}", c, t)),
+        }
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(&Token::Punct(c)) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn ident(&mut self) -> Result<String, String> {
+        match self.next()? {
+            Token::Ident(s) => Ok(s),
+            t => Err(format!("expected identifier, found {:?}", t)),
+        }
+    }
+
+    // comma separated values up to `close`, trailing comma allowed
+    fn list(&mut self, close: char) -> Result<Vec<Value>, String> {
+        let mut items = Vec::new();
+        while !self.eat(close) {
+            items.push(self.value()?);
+            if !self.eat(',') {
+                self.expect(close)?;
+                break;
+            }
+        }
+        Ok(items)
+    }
+
+    // literal, `UXXX::from_be_hex("..")`, constant name, struct, array or tuple
+    fn value(&mut self) -> Result<Value, String> {
+        match self.next()? {
+            Token::Number(n) => {
+                let digits: String = n.chars().take_while(|c| c.is_ascii_digit() || *c == '_').filter(|c| *c != '_').collect();
+                BigUint::parse_bytes(digits.as_bytes(), 10)
+                    .map(Value::Int)
+                    .ok_or_else(|| format!("bad integer literal {}", n))
+            }
+            Token::Punct('[') => Ok(Value::List(self.list(']')?)),
+            Token::Punct('(') => Ok(Value::List(self.list(')')?)),
+            Token::Ident(name) => {
+                if self.eat(':') {
+                    self.expect(':')?;
+                    let func = self.ident()?;
+                    if func != "from_be_hex" {
+                        return Err(format!("unsupported constructor {}::{}", name, func));
+                    }
+                    self.expect('(')?;
+                    let hex = match self.next()? {
+                        Token::Str(s) => s,
+                        t => return Err(format!("expected hex string, found {:?}", t)),
+                    };
+                    self.expect(')')?;
+                    BigUint::parse_bytes(hex.as_bytes(), 16)
+                        .map(Value::Int)
+                        .ok_or_else(|| format!("bad hex literal in {}", name))
+                } else if self.eat('{') {
+                    let mut fields = Vec::new();
+                    while !self.eat('}') {
+                        let field = self.ident()?;
+                        self.expect(':')?;
+                        fields.push((field, self.value()?));
+                        if !self.eat(',') {
+                            self.expect('}')?;
+                            break;
+                        }
+                    }
+                    Ok(Value::Struct(fields))
+                } else {
+                    Ok(Value::Const(name))
+                }
+            }
+            t => Err(format!("unexpected {:?}", t)),
+        }
+    }
+
+    // skips a `struct` or `fn` item up to its closing brace, their generics use `const` too
+    fn skip_item(&mut self) -> Result<(), String> {
+        while !self.eat('{') {
+            self.next()?;
+        }
+        let mut depth = 1;
+        while depth > 0 {
+            match self.next()? {
+                Token::Punct('{') => depth += 1,
+                Token::Punct('}') => depth -= 1,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    // every `const NAME: Type = value;` and `static NAME: Type = value;` in the file, anything
+    // else is skipped
+    fn consts(&mut self) -> Result<HashMap<String, Value>, String> {
+        let mut consts = HashMap::new();
+        while let Some(token) = self.peek() {
+            let Token::Ident(keyword) = token else {
+                self.pos += 1;
+                continue;
+            };
+            match keyword.as_str() {
+                "struct" | "fn" => {
+                    self.skip_item()?;
+                    continue;
+                }
+                "const" | "static" => self.pos += 1,
+                _ => {
+                    self.pos += 1;
+                    continue;
+                }
+            }
+            let name = self.ident()?;
+            while !self.eat('=') {
+                self.next()?;
+            }
+            let value = self.value()?;
+            self.expect(';')?;
+            consts.insert(name, value);
+        }
+        Ok(consts)
+    }
+}
+
+// resolves dotted field paths and constant references in the parsed table
+struct Lookup<'a> {
+    consts: &'a HashMap<String, Value>,
+}
+
+impl Lookup<'_> {
+    fn resolve<'v>(&'v self, value: &'v Value) -> Result<&'v Value, String> {
+        match value {
+            Value::Const(name) => self.consts.get(name).ok_or_else(|| format!("unknown constant {}", name)),
+            v => Ok(v),
+        }
+    }
+
+    fn field<'v>(&'v self, value: &'v Value, path: &str) -> Result<&'v Value, String> {
+        let mut current = self.resolve(value)?;
+        for part in path.split('.') {
+            let Value::Struct(fields) = current else {
+                return Err(format!("{} is not a struct", path));
+            };
+            let (_, v) = fields.iter().find(|(name, _)| name == part).ok_or_else(|| format!("missing field {}", path))?;
+            current = self.resolve(v)?;
+        }
+        Ok(current)
+    }
+
+    fn int(&self, value: &Value, path: &str) -> Result<BigUint, String> {
+        match self.field(value, path)? {
+            Value::Int(v) => Ok(v.clone()),
+            _ => Err(format!("{} is not an integer", path)),
+        }
+    }
+
+    fn named_int(&self, name: &str) -> Result<BigUint, String> {
+        match self.consts.get(name).map(|v| self.resolve(v)) {
+            Some(Ok(Value::Int(v))) => Ok(v.clone()),
+            _ => Err(format!("missing integer constant {}", name)),
+        }
+    }
+
+    fn list(&self, name: &str) -> Result<&[Value], String> {
+        match self.consts.get(name) {
+            Some(Value::List(items)) => Ok(items),
+            _ => Err(format!("missing array constant {}", name)),
+        }
+    }
+}
+
+fn to_u16(v: &BigUint, what: &str) -> Result<u16, String> {
+    u16::try_from(v).map_err(|_| format!("{} does not fit in u16", what))
+}
+
+struct Entry {
+    ecc_bits: u16,
+    rsa_bits: u16,
+    session_key: BigUint,
+    exponent: BigUint,
+    p: BigUint,
+    q: BigUint,
+    d: BigUint,
+    dp: BigUint,
+    dq: BigUint,
+    qinv: BigUint,
+    a: BigUint,
+    b: BigUint,
+    ecc_prime: BigUint,
+    order: BigUint,
+    cofactor: BigUint,
+    gen_x: BigUint,
+    gen_y: BigUint,
+    private_keys: [BigUint; 2],
+}
+
+fn parse_entry(lookup: &Lookup, value: &Value) -> Result<Entry, String> {
+    Ok(Entry {
+        ecc_bits: to_u16(&lookup.int(value, "ecc_bits")?, "ecc_bits")?,
+        rsa_bits: to_u16(&lookup.int(value, "rsa_bits")?, "rsa_bits")?,
+        session_key: lookup.int(value, "rsa.session_key")?,
+        exponent: lookup.int(value, "rsa.exponent")?,
+        p: lookup.int(value, "rsa.p")?,
+        q: lookup.int(value, "rsa.q")?,
+        d: lookup.int(value, "rsa.d")?,
+        dp: lookup.int(value, "rsa.dp")?,
+        dq: lookup.int(value, "rsa.dq")?,
+        qinv: lookup.int(value, "rsa.qinv")?,
+        a: lookup.int(value, "ecc.curve.a")?,
+        b: lookup.int(value, "ecc.curve.b")?,
+        ecc_prime: lookup.int(value, "ecc.curve.p")?,
+        order: lookup.int(value, "ecc.curve.order")?,
+        cofactor: lookup.int(value, "ecc.curve.cofactor")?,
+        gen_x: lookup.int(value, "ecc.curve.generator.x")?,
+        gen_y: lookup.int(value, "ecc.curve.generator.y")?,
+        private_keys: [lookup.int(value, "ecc.private_key1")?, lookup.int(value, "ecc.private_key2")?],
+    })
+}
+
+// every invariant primegen guarantees for one entry, returns the violated ones
+fn check_entry(e: &Entry, row: (u16, u16)) -> Vec<String> {
+    let mut errors = Vec::new();
+    let one = BigUint::one();
+
+    if (e.ecc_bits, e.rsa_bits) != row {
+        errors.push(format!("key sizes {}/{} do not match ECC_V_RSA row {}/{}", e.ecc_bits, e.rsa_bits, row.0, row.1));
+    }
+
+    // RSA
+    let rsa_prime_bits = (e.rsa_bits / 2) as u64;
+    for (name, prime) in [("p", &e.p), ("q", &e.q)] {
+        if !PRIMALITY.is_prime(prime) {
+            errors.push(format!("RSA {} is not prime", name));
+        }
+        if prime.bits() != rsa_prime_bits {
+            errors.push(format!("RSA {} has {} bits, expected {}", name, prime.bits(), rsa_prime_bits));
+        }
+    }
+    if e.p == e.q {
+        errors.push("RSA p == q".to_string());
+    }
+    if e.p > one && e.q > one {
+        let totient = (&e.p - &one) * (&e.q - &one);
+        if !e.exponent.gcd(&totient).is_one() {
+            errors.push(format!("gcd(e, phi) != 1 for e = {}", e.exponent));
+        }
+
+        // precomputed private key
+        if (&e.exponent * &e.d) % &totient != one || e.d >= totient {
+            errors.push("d is not e^-1 mod phi".to_string());
+        }
+        if e.dp != &e.d % (&e.p - &one) {
+            errors.push("dp != d mod (p - 1)".to_string());
+        }
+        if e.dq != &e.d % (&e.q - &one) {
+            errors.push("dq != d mod (q - 1)".to_string());
+        }
+        if (&e.q * &e.qinv) % &e.p != one || e.qinv >= e.p {
+            errors.push("qinv is not q^-1 mod p".to_string());
+        }
+    }
+    let n = &e.p * &e.q;
+    if e.session_key < BigUint::from(2u32) {
+        errors.push("session key is below 2".to_string());
+    }
+    if e.session_key >= n {
+        errors.push("session key is not below n".to_string());
+    }
+
+    // ECC
+    let p = &e.ecc_prime;
+    if !PRIMALITY.is_prime(p) {
+        errors.push("ECC prime is not prime".to_string());
+    } else if !PRIMALITY.is_prime(&(p >> 1usize)) {
+        errors.push("ECC prime is not a safe prime".to_string());
+    }
+    if p.bits() != e.ecc_bits as u64 {
+        errors.push(format!("ECC prime has {} bits, expected {}", p.bits(), e.ecc_bits));
+    }
+    if p.is_zero() {
+        return errors;
+    }
+
+    // non-singular: 4a^3 + 27b^2 != 0 mod p
+    let disc = (BigUint::from(4u32) * e.a.modpow(&BigUint::from(3u32), p) + BigUint::from(27u32) * &e.b * &e.b) % p;
+    if disc.is_zero() {
+        errors.push("curve is singular (4a^3 + 27b^2 = 0 mod p)".to_string());
+    }
+
+    errors.extend(check_subgroup(p, (&e.a, &e.b), (&e.gen_x, &e.gen_y), &e.order, &e.cofactor));
+
+    for (i, key) in e.private_keys.iter().enumerate() {
+        if key.is_zero() || *key >= e.order {
+            errors.push(format!("ECC private key {} is not in [1, order)", i + 1));
+        }
+    }
+
+    errors
+}
+
+// G generates a subgroup of prime order n and h * n is the curve order: [n]G = O makes n the order of G,
+// and with n > 4 sqrt(p) only one multiple of n lies in the Hasse interval
+fn check_subgroup(
+    p: &BigUint,
+    (a, b): (&BigUint, &BigUint),
+    (x, y): (&BigUint, &BigUint),
+    order: &BigUint,
+    cofactor: &BigUint,
+) -> Vec<String> {
+    let mut errors = Vec::new();
+    let one = BigUint::one();
+
+    let rhs = (x.modpow(&BigUint::from(3u32), p) + a * x + b) % p;
+    if x >= p || y >= p || (y * y) % p != rhs {
+        errors.push(format!("generator ({:x}, {:x}) is not on the curve", x, y));
+    } else if !order.is_zero() && scalar_mul(order, &Some((x.clone(), y.clone())), p).is_some() {
+        errors.push(format!("[order]G is not the point at infinity for order {:x}", order));
+    }
+
+    if !PRIMALITY.is_prime(order) {
+        errors.push(format!("order {:x} is not prime", order));
+    }
+    // n^2 > 16p, squared to stay in integers
+    if order * order <= BigUint::from(16u32) * p {
+        errors.push(format!("order {:x} is not above 4 sqrt(p), the cofactor is ambiguous", order));
+    }
+    if cofactor.is_zero() || *cofactor > BigUint::from(MAX_COFACTOR) {
+        errors.push(format!("cofactor {} is not in [1, {}]", cofactor, MAX_COFACTOR));
+    }
+    // Hasse: |#E - (p + 1)| <= 2 sqrt(p), squared to stay in integers
+    let curve_order = order * cofactor;
+    let trace = if curve_order > p + &one { &curve_order - (p + &one) } else { (p + &one) - &curve_order };
+    if &trace * &trace > BigUint::from(4u32) * p {
+        errors.push(format!("order * cofactor = {:x} is outside the Hasse interval", curve_order));
+    }
+
+    errors
+}
+
+// the generator comb has to be exactly what primegen would build for this entry
+fn check_comb(lookup: &Lookup, e: &Entry, teeth: u8, comb: &Value) -> Vec<String> {
+    let Value::List(points) = comb else {
+        return vec!["generator comb is not an array".to_string()];
+    };
+    let points = match points.iter().map(|v| Ok((lookup.int(v, "x")?, lookup.int(v, "y")?))).collect::<Result<Vec<_>, String>>() {
+        Ok(points) => points,
+        Err(e) => return vec![format!("unreadable generator comb: {}", e)],
+    };
+    let expected = comb_table(&Some((e.gen_x.clone(), e.gen_y.clone())), &e.order, teeth, &e.ecc_prime);
+    if expected.as_ref() != Some(&points) {
+        return vec![format!("generator comb does not match {} teeth over the generator", teeth)];
+    }
+    Vec::new()
+}
+
+fn load(path: &Path) -> Result<HashMap<String, Value>, String> {
+    let src = fs::read_to_string(path).map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+    let mut parser = Parser { tokens: tokenize(&src)?, pos: 0 };
+    parser.consts()
+}
+
+// audits a generated lookup.rs, prints every violation and returns whether the table is valid
+pub fn verify(path: &Path) -> bool {
+    let consts = match load(path) {
+        Ok(consts) => consts,
+        Err(e) => {
+            eprintln!("Could not parse {}: {}", path.display(), e);
+            return false;
+        }
+    };
+    let lookup = Lookup { consts: &consts };
+
+    let layout = (|| -> Result<_, String> {
+        let trials = lookup.named_int("TRIALS")?;
+        let trials = usize::from(to_u16(&trials, "TRIALS")?);
+        let rows = lookup
+            .list("ECC_V_RSA")?
+            .iter()
+            .map(|row| match row {
+                Value::List(pair) if pair.len() == 2 => match (&pair[0], &pair[1]) {
+                    (Value::Int(ecc), Value::Int(rsa)) => Ok((to_u16(ecc, "ECC bits")?, to_u16(rsa, "RSA bits")?)),
+                    _ => Err("ECC_V_RSA rows must be integer pairs".to_string()),
+                },
+                _ => Err("ECC_V_RSA rows must be pairs".to_string()),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let tables = (0..rows.len()).map(|i| lookup.list(&format!("ROW_{}", i))).collect::<Result<Vec<_>, _>>()?;
+        // tables from before --comb-teeth have no combs to check
+        let combs = match lookup.named_int("COMB_TEETH") {
+            Ok(teeth) => {
+                let teeth = u8::try_from(&teeth).map_err(|_| "COMB_TEETH does not fit in u8".to_string())?;
+                let combs = (0..rows.len()).map(|i| lookup.list(&format!("COMB_{}", i))).collect::<Result<Vec<_>, _>>()?;
+                Some((teeth, combs))
+            }
+            Err(_) => None,
+        };
+        Ok((trials, rows, tables, combs))
+    })();
+    let (trials, rows, tables, combs) = match layout {
+        Ok(layout) => layout,
+        Err(e) => {
+            eprintln!("Invalid table layout in {}: {}", path.display(), e);
+            return false;
+        }
+    };
+
+    let mut violations = 0usize;
+    let mut total = 0usize;
+    for (size_idx, (&row, table)) in rows.iter().zip(&tables).enumerate() {
+        if table.len() != trials {
+            eprintln!("ROW_{} has {} entries, expected {} trials", size_idx, table.len(), trials);
+            violations += 1;
+        }
+        total += table.len();
+
+        let comb = combs.as_ref().map(|(teeth, combs)| (*teeth, combs[size_idx]));
+        if let Some((_, comb)) = comb.filter(|(_, comb)| comb.len() != trials) {
+            eprintln!("COMB_{} has {} entries, expected {} trials", size_idx, comb.len(), trials);
+            violations += 1;
+        }
+
+        for (trial, value) in table.iter().enumerate() {
+            let errors = match parse_entry(&lookup, value) {
+                Ok(entry) => {
+                    let mut errors = check_entry(&entry, row);
+                    // the comb is rebuilt from the curve, which has to be sound first
+                    let comb = comb.and_then(|(teeth, comb)| Some((teeth, comb.get(trial)?)));
+                    if let Some((teeth, comb)) = comb.filter(|_| errors.is_empty()) {
+                        errors.extend(check_comb(&lookup, &entry, teeth, comb));
+                    }
+                    errors
+                }
+                Err(e) => vec![format!("unreadable entry: {}", e)],
+            };
+            for error in &errors {
+                eprintln!("[size {}, trial {}] ECC {} / RSA {}: {}", size_idx, trial, row.0, row.1, error);
+            }
+            violations += errors.len();
+        }
+    }
+
+    if violations == 0 {
+        println!("{}: all {} entries passed ({} rows x {} trials)", path.display(), total, rows.len(), trials);
+    } else {
+        println!("{}: {} violation(s) found", path.display(), violations);
+    }
+    violations == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(s: &str) -> BigUint {
+        BigUint::parse_bytes(s.as_bytes(), 16).unwrap()
+    }
+
+    // (p, G, n, h) from a generated table, y^2 = x^3 + 2x + 3
+    fn rows() -> [(BigUint, (BigUint, BigUint), BigUint, BigUint); 2] {
+        [
+            (hex("98b7b133"), (hex("4fc33cec"), hex("6867a46b")), hex("4c5b21ed"), BigUint::from(2u32)),
+            (
+                hex("f7a957ebcdddd0d3"),
+                (hex("35ac99ce930c8941"), hex("4382d268fd41ca02")),
+                hex("3dea55faf9286f43"),
+                BigUint::from(4u32),
+            ),
+        ]
+    }
+
+    fn check(p: &BigUint, (x, y): &(BigUint, BigUint), order: &BigUint, cofactor: &BigUint) -> Vec<String> {
+        let (a, b) = (BigUint::from(2u32), BigUint::from(3u32));
+        check_subgroup(p, (&a, &b), (x, y), order, cofactor)
+    }
+
+    #[test]
+    fn generated_subgroups_pass() {
+        for (p, g, order, cofactor) in rows() {
+            assert_eq!(check(&p, &g, &order, &cofactor), Vec::<String>::new());
+        }
+    }
+
+    #[test]
+    fn tampered_cofactor_or_order_fails() {
+        for (p, g, order, cofactor) in rows() {
+            assert!(!check(&p, &g, &order, &(&cofactor + 1u32)).is_empty());
+            assert!(!check(&p, &g, &(&order + 2u32), &cofactor).is_empty());
+            assert!(!check(&p, &g, &(&order * &cofactor), &BigUint::one()).is_empty());
+        }
+    }
+
+    // over F_23 the curve has 24 points, but G = (8, 5) has order 3 and 5 * 3 = 15 is inside the Hasse
+    // interval too: only the bound on n tells this pair apart from a real one
+    #[test]
+    fn small_order_is_ambiguous() {
+        let p = BigUint::from(23u32);
+        let g = (BigUint::from(8u32), BigUint::from(5u32));
+        let errors = check(&p, &g, &BigUint::from(3u32), &BigUint::from(5u32));
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert!(errors[0].contains("4 sqrt(p)"), "{:?}", errors);
+    }
+}